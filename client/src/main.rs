@@ -1,22 +1,26 @@
 use sfml::{
     SfBox, SfError,
     graphics::{
-        Color, Font, Rect, RenderTarget, RenderWindow, Sprite, Texture, Transformable, View,
+        Color, Font, Rect, RectangleShape, RenderTarget, RenderWindow, Shape, Sprite, Texture,
+        Transformable, View,
     },
-    system::{Vector2, Vector2i, Vector2u},
+    system::{Vector2, Vector2f, Vector2i, Vector2u},
     window,
     window::{ContextSettings, Event, Key},
 };
 use signals::{
-    AssetData, AssetId, Auth, Authorized, ClientUpdate, Entity, EntityId, PlayerSignal, Position,
-    Tile,
+    AssetData, AssetId, Auth, AuthReply, Authorized, Beat, ClientUpdate, DAMAGE_STAGES, Direction,
+    Entity, EntityId, EquipSlot, FrameReader, FrameWriter, Groove, Inventory, PlayerSignal,
+    Position, ServerSignal, StampedSignal, Tile, WallTile, write_frame,
 };
 use std::{
-    collections::HashMap,
-    fs, io,
+    collections::{HashMap, HashSet},
+    fs,
+    hash::Hash,
+    io,
     net::{Ipv4Addr, SocketAddr, TcpStream},
     str::FromStr,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 mod logger;
@@ -26,6 +30,18 @@ use logger::*;
 const TILE_SIZE: u32 = 12;
 const FIELD_SIZE: Vector2u = Vector2u::new(39, 25);
 const BG_COLOR: Color = Color::rgb(0x11, 0x0a, 0x03);
+const SLOT_COLOR: Color = Color::rgb(0x55, 0x44, 0x33);
+const SELECTED_SLOT_COLOR: Color = Color::rgb(0xee, 0xcc, 0x55);
+const CORPSE_COLOR: Color = Color::rgb(0x66, 0x66, 0x66);
+const HEALTH_COLOR: Color = Color::rgb(0xcc, 0x22, 0x22);
+const MISSING_HEALTH_COLOR: Color = Color::rgb(0x33, 0x11, 0x11);
+// multiplies the colors of tiles out of view
+const STALE_COLOR: Color = Color::rgb(0x77, 0x77, 0x88);
+// how much of a tile's color is left in complete darkness
+const MIN_LIGHT: u8 = 0x30;
+const BEAT_COLOR: Color = Color::rgb(0xee, 0x55, 0x77);
+// in the order they're drawn after the bag and unequipped with Shift+1, Shift+2, ...
+const EQUIP_SLOTS: [EquipSlot; 3] = [EquipSlot::Weapon, EquipSlot::DiggingTool, EquipSlot::Torch];
 
 struct Asset {
     texture: SfBox<Texture>,
@@ -45,12 +61,28 @@ impl TryFrom<AssetData> for Asset {
     }
 }
 
+fn apply<K: Eq + Hash, V>(map: &mut HashMap<K, V>, changes: Box<[(K, Option<V>)]>) {
+    for (key, value) in changes {
+        match value {
+            Some(value) => map.insert(key, value),
+            None => map.remove(&key),
+        };
+    }
+}
+
 #[derive(Default)]
 struct State {
     assets: HashMap<AssetId, Asset>,
     floors: HashMap<Position, Tile>,
-    walls: HashMap<Position, Tile>,
+    walls: HashMap<Position, WallTile>,
+    decorations: HashMap<Position, Tile>,
     entities: HashMap<EntityId, Entity>,
+    items: HashMap<Position, Tile>,
+    // out of view, shown as they were last seen
+    stale: HashSet<Position>,
+    // tiles that aren't here are unlit
+    light: HashMap<Position, u8>,
+    inventory: Option<Inventory>,
 }
 
 impl State {
@@ -60,7 +92,11 @@ impl State {
             assets,
             floors,
             walls,
+            decorations,
             entities,
+            items,
+            stale,
+            light,
         }: ClientUpdate,
     ) {
         self.assets
@@ -69,9 +105,56 @@ impl State {
                 Some((id, asset))
             }));
 
-        self.floors.extend(floors);
-        self.walls.extend(walls);
-        self.entities.extend(entities);
+        apply(&mut self.floors, floors);
+        apply(&mut self.walls, walls);
+        apply(&mut self.decorations, decorations);
+        apply(&mut self.entities, entities);
+        apply(&mut self.items, items);
+
+        for (pos, is_stale) in stale {
+            if is_stale {
+                self.stale.insert(pos);
+            } else {
+                self.stale.remove(&pos);
+            }
+        }
+
+        for (pos, level) in light {
+            if level == 0 {
+                self.light.remove(&pos);
+            } else {
+                self.light.insert(pos, level);
+            }
+        }
+    }
+}
+
+fn light_color(level: u8) -> Color {
+    let shade = MIN_LIGHT + (u16::from(level) * u16::from(u8::MAX - MIN_LIGHT) / 255) as u8;
+    Color::rgb(shade, shade, shade)
+}
+
+fn key_to_direction(code: Key) -> Option<Direction> {
+    match code {
+        Key::Up | Key::W => Some(Direction::Up),
+        Key::Down | Key::S => Some(Direction::Down),
+        Key::Left | Key::A => Some(Direction::Left),
+        Key::Right | Key::D => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+fn key_to_slot(code: Key) -> Option<usize> {
+    match code {
+        Key::Num1 => Some(0),
+        Key::Num2 => Some(1),
+        Key::Num3 => Some(2),
+        Key::Num4 => Some(3),
+        Key::Num5 => Some(4),
+        Key::Num6 => Some(5),
+        Key::Num7 => Some(6),
+        Key::Num8 => Some(7),
+        _ => None,
     }
 }
 
@@ -79,6 +162,14 @@ struct Client {
     window: SfBox<RenderWindow>,
     logger: Logger,
     state: State,
+    // `None` while spectating
+    player_entity_id: Option<EntityId>,
+    selected_slot: usize,
+    // the last beat and when it was received, to estimate the server time with
+    beat: Option<(Beat, Instant)>,
+    groove: Option<Groove>,
+    // signals wait here until the non-blocking stream takes them
+    writer: FrameWriter,
 }
 
 impl Client {
@@ -107,6 +198,11 @@ impl Client {
             window,
             logger,
             state: State::default(),
+            player_entity_id: None,
+            selected_slot: 0,
+            beat: None,
+            groove: None,
+            writer: FrameWriter::default(),
         }
     }
 
@@ -120,23 +216,43 @@ impl Client {
             self.window.display();
 
             self.state = State::default();
+            self.beat = None;
+            self.groove = None;
+            self.writer = FrameWriter::default();
+            let mut reader = FrameReader::default();
             let maybe_connected = TcpStream::connect_timeout(&server_addr, CONNECT_TIMEOUT)
                 .map_err(|error| error.to_string())
                 .and_then(|mut stream| {
+                    write_frame(&mut stream, &auth).map_err(|error| error.to_string())?;
+
+                    // the stream is still blocking, so this waits for the whole reply
+                    let reply = loop {
+                        let maybe_reply = reader
+                            .read(&mut stream)
+                            .map_err(|error| error.to_string())?;
+                        if let Some(reply) = maybe_reply {
+                            break reply;
+                        }
+                    };
+
                     let Authorized {
                         player_entity_id,
                         update,
-                    } = bincode::serialize_into(&mut stream, &auth)
-                        .and_then(|_| bincode::deserialize_from(&mut stream))
-                        .map_err(|error| error.to_string())?;
+                    } = match reply {
+                        AuthReply::Authorized(authorized) => authorized,
+                        AuthReply::Rejected { reason } => return Err(reason.to_string()),
+                    };
 
                     self.state.update(update);
 
                     Ok((stream, player_entity_id))
                 });
 
-            let (mut stream, _player_entity_id) = match maybe_connected {
-                Ok(connected) => connected,
+            let mut stream = match maybe_connected {
+                Ok((stream, player_entity_id)) => {
+                    self.set_player_entity(player_entity_id);
+                    stream
+                }
 
                 Err(error) => {
                     self.logger.push(error.to_string());
@@ -164,23 +280,34 @@ impl Client {
                 }
             };
 
+            if let Err(error) = stream.set_nonblocking(true) {
+                self.logger.push(error.to_string());
+            }
+
             while self.window.is_open() {
                 while let Some(event) = self.window.poll_event() {
                     match event {
                         Event::Closed => self.window.close(),
 
-                        Event::KeyPressed { code, ctrl, .. } => {
+                        Event::KeyPressed {
+                            code,
+                            ctrl,
+                            shift,
+                            alt,
+                            ..
+                        } => {
                             if ctrl && code == Key::R {
-                                match bincode::serialize_into(
-                                    &mut stream,
-                                    &PlayerSignal::ReloadServer,
-                                ) {
-                                    Ok(_) => continue 'reload,
-
-                                    Err(error) => {
-                                        self.logger.push_if_unique(error.to_string());
-                                    }
+                                // the server reloads in place, so only reconnect if it's gone
+                                self.send_signal(PlayerSignal::ReloadServer);
+                                if self.writer.flush(&mut stream).is_err() {
+                                    continue 'reload;
                                 }
+                            } else if let Some(direction) = key_to_direction(code) {
+                                self.handle_direction(direction, shift, ctrl, alt);
+                            } else if let Some(index) = key_to_slot(code) {
+                                self.handle_slot(index, shift, alt);
+                            } else if let Some(signal) = self.key_to_signal(code) {
+                                self.send_signal(signal);
                             }
                         }
 
@@ -188,6 +315,11 @@ impl Client {
                     }
                 }
 
+                if let Err(error) = self.writer.flush(&mut stream) {
+                    self.logger.push_if_unique(error.to_string());
+                }
+                self.receive_signals(&mut stream, &mut reader);
+
                 self.window.clear(BG_COLOR);
                 self.draw();
                 self.window.display();
@@ -197,36 +329,335 @@ impl Client {
         }
     }
 
-    fn draw(&mut self) {
-        let posed_textures = {
-            let tiles = (self.state.floors.iter())
-                .chain(&self.state.walls)
-                .map(|(&pos, tile)| (pos, tile.asset_id));
+    fn receive_signals(&mut self, stream: &mut TcpStream, reader: &mut FrameReader) {
+        loop {
+            match reader.read(stream) {
+                Ok(Some(ServerSignal::Update(update))) => self.state.update(update),
+
+                Ok(Some(ServerSignal::Resync(Authorized {
+                    player_entity_id,
+                    update,
+                }))) => {
+                    self.logger.clear();
+                    self.state = State::default();
+                    self.state.update(update);
+                    self.set_player_entity(player_entity_id);
+                }
+
+                Ok(Some(ServerSignal::MoveRejected(error))) => {
+                    self.logger
+                        .push_if_unique(format!("move rejected: {:?}", error));
+                }
+
+                Ok(Some(ServerSignal::DigRejected(error))) => {
+                    self.logger
+                        .push_if_unique(format!("dig rejected: {:?}", error));
+                }
+
+                Ok(Some(ServerSignal::InteractRejected(error))) => {
+                    self.logger
+                        .push_if_unique(format!("interaction rejected: {:?}", error));
+                }
+
+                Ok(Some(ServerSignal::AttackRejected(error))) => {
+                    self.logger
+                        .push_if_unique(format!("attack rejected: {:?}", error));
+                }
+
+                Ok(Some(ServerSignal::Beat(beat))) => self.beat = Some((beat, Instant::now())),
+
+                Ok(Some(ServerSignal::OffBeat)) => {
+                    self.logger.push_if_unique("off beat".to_owned());
+                }
+
+                Ok(Some(ServerSignal::Groove(groove))) => self.groove = Some(groove),
+
+                Ok(Some(ServerSignal::Inventory(inventory))) => {
+                    self.state.inventory = Some(inventory);
+                }
+
+                Ok(Some(ServerSignal::InventoryRejected(error))) => {
+                    self.logger
+                        .push_if_unique(format!("inventory: {:?}", error));
+                }
+
+                Ok(Some(ServerSignal::ReloadFailed(error))) => {
+                    self.logger
+                        .push_if_unique(format!("reload failed: {}", error));
+                }
+
+                Ok(None) => break,
+
+                Err(error) => {
+                    self.logger.push_if_unique(error.to_string());
+                    break;
+                }
+            }
+        }
+    }
+
+    fn set_player_entity(&mut self, player_entity_id: Option<EntityId>) {
+        if player_entity_id.is_none() {
+            self.logger.push_if_unique("spectating".to_owned());
+        }
+
+        self.player_entity_id = player_entity_id;
+    }
+
+    // the latency makes it lag behind a bit
+    fn server_time(&self) -> u64 {
+        self.beat.map_or(0, |(beat, received_at)| {
+            let elapsed = u64::try_from(received_at.elapsed().as_millis()).unwrap_or(u64::MAX);
+            beat.timestamp.saturating_add(elapsed)
+        })
+    }
 
-            let entities =
-                (self.state.entities.values()).map(|entity| (entity.pos, entity.asset_id));
+    fn stamp(&self, signal: PlayerSignal) -> StampedSignal {
+        StampedSignal {
+            timestamp: self.server_time(),
+            signal,
+        }
+    }
 
-            tiles.chain(entities)
+    /// Only queues the signal, it's sent by flushing `writer`
+    fn send_signal(&mut self, signal: PlayerSignal) {
+        let signal = self.stamp(signal);
+        if let Err(error) = self.writer.push(&signal) {
+            self.logger.push_if_unique(error.to_string());
         }
-        .filter_map(|(pos, asset_id)| match self.state.assets.get(&asset_id) {
-            Some(Asset { texture }) => Some((pos, texture)),
+    }
 
-            None => {
-                self.logger
-                    .push_if_unique(format!("no asset for {:?}", asset_id));
-                None
+    fn key_to_signal(&self, code: Key) -> Option<PlayerSignal> {
+        match code {
+            Key::F => Some(PlayerSignal::Equip(self.selected_slot)),
+            Key::G => Some(PlayerSignal::Drop(self.selected_slot)),
+            Key::E => Some(PlayerSignal::PickUp),
+            _ => None,
+        }
+    }
+
+    fn handle_slot(&mut self, index: usize, shift: bool, alt: bool) {
+        if shift {
+            if let Some(&slot) = EQUIP_SLOTS.get(index) {
+                self.send_signal(PlayerSignal::Unequip(slot));
             }
-        });
+        } else if alt {
+            // moves the selected item to `index`
+            self.send_signal(PlayerSignal::Swap(self.selected_slot, index));
+            self.selected_slot = index;
+        } else {
+            self.selected_slot = index;
+        }
+    }
+
+    fn handle_direction(&mut self, direction: Direction, dig: bool, interact: bool, attack: bool) {
+        if self.player_entity_id.is_some() {
+            let signal = if attack {
+                PlayerSignal::Attack(direction)
+            } else if interact {
+                PlayerSignal::Interact(direction)
+            } else if dig {
+                PlayerSignal::Dig(direction)
+            } else {
+                PlayerSignal::Move(direction)
+            };
+
+            self.send_signal(signal);
+            return;
+        }
+
+        // spectators move the camera freely
+        let offset = match direction {
+            Direction::Up => Vector2i::new(0, -1),
+            Direction::Down => Vector2i::new(0, 1),
+            Direction::Left => Vector2i::new(-1, 0),
+            Direction::Right => Vector2i::new(1, 0),
+        } * TILE_SIZE as i32;
+
+        let mut view = self.window.view().to_owned();
+        view.move_(offset.as_other());
+        self.window.set_view(&view);
+    }
+
+    fn draw(&mut self) {
+        let posed_textures = {
+            let floors =
+                (self.state.floors.iter()).map(|(&pos, tile)| (pos, tile.asset_id, Color::WHITE));
+
+            let walls = (self.state.walls.iter()).map(|(&pos, wall)| {
+                // damaged walls get darker until they break
+                let brightness = 0xff - 0xff / 2 / DAMAGE_STAGES * wall.damage_stage;
+                (
+                    pos,
+                    wall.asset_id,
+                    Color::rgb(brightness, brightness, brightness),
+                )
+            });
+
+            let decorations = (self.state.decorations.iter())
+                .map(|(&pos, tile)| (pos, tile.asset_id, Color::WHITE));
+
+            let items =
+                (self.state.items.iter()).map(|(&pos, tile)| (pos, tile.asset_id, Color::WHITE));
+
+            let entities = (self.state.entities.values()).map(|entity| {
+                let is_corpse = entity.health.is_some_and(|health| health.current == 0);
+                let color = if is_corpse {
+                    CORPSE_COLOR
+                } else {
+                    Color::WHITE
+                };
+                (entity.pos, entity.asset_id, color)
+            });
+
+            floors
+                .chain(walls)
+                .chain(decorations)
+                .chain(items)
+                .chain(entities)
+        }
+        .filter_map(
+            |(pos, asset_id, color)| match self.state.assets.get(&asset_id) {
+                Some(Asset { texture }) => Some((pos, texture, color)),
+
+                None => {
+                    self.logger
+                        .push_if_unique(format!("no asset for {:?}", asset_id));
+                    None
+                }
+            },
+        );
+
+        for (pos, texture, color) in posed_textures {
+            let level = self.state.light.get(&pos).copied().unwrap_or_default();
+            let color = color * light_color(level);
+            let color = if self.state.stale.contains(&pos) {
+                color * STALE_COLOR
+            } else {
+                color
+            };
+            let Position { x, y } = pos;
 
-        for (Position { x, y }, texture) in posed_textures {
             let mut sprite = Sprite::with_texture(texture);
+            sprite.set_color(color);
             sprite
                 .set_position(Vector2i::new(x * TILE_SIZE as i32, y * TILE_SIZE as i32).as_other());
             self.window.draw(&sprite);
         }
 
+        self.draw_health_bars();
+        self.draw_inventory();
+        self.draw_beat();
         self.window.draw(&self.logger);
     }
+
+    // only for the wounded, above their heads
+    fn draw_health_bars(&mut self) {
+        let tile_size = TILE_SIZE as f32;
+
+        for entity in self.state.entities.values() {
+            let Some(health) = entity.health else {
+                continue;
+            };
+            if health.current == 0 || health.current >= health.max {
+                continue;
+            }
+
+            let pos = Vector2i::new(entity.pos.x, entity.pos.y).as_other::<f32>() * tile_size
+                - Vector2f::new(0., 3.);
+            let filled = tile_size * health.current as f32 / health.max as f32;
+
+            let mut bar = RectangleShape::with_size(Vector2f::new(tile_size, 2.));
+            bar.set_position(pos);
+            bar.set_fill_color(MISSING_HEALTH_COLOR);
+            self.window.draw(&bar);
+
+            bar.set_size(Vector2f::new(filled, 2.));
+            bar.set_fill_color(HEALTH_COLOR);
+            self.window.draw(&bar);
+        }
+    }
+
+    // fades out after every beat in the bottom right corner of the view, with a pip per groove multiplier to the left of it
+    fn draw_beat(&mut self) {
+        let Some((beat, _)) = self.beat else {
+            return;
+        };
+
+        let bottom_right = {
+            let view = self.window.view();
+            view.center() + view.size() / 2.
+        };
+        let tile_size = TILE_SIZE as f32;
+
+        let phase = (self.server_time() % beat.interval) as f32 / beat.interval as f32;
+        let mut color = BEAT_COLOR;
+        color.a = (f32::from(u8::MAX) * (1. - phase)) as u8;
+
+        let mut indicator = RectangleShape::with_size(Vector2f::new(tile_size, tile_size));
+        indicator.set_position(bottom_right - Vector2f::new(tile_size + 2., tile_size + 2.));
+        indicator.set_fill_color(color);
+        indicator.set_outline_thickness(1.);
+        indicator.set_outline_color(BEAT_COLOR);
+        self.window.draw(&indicator);
+
+        let multiplier = self.groove.map_or(1, |groove| groove.multiplier);
+        for index in 0..multiplier {
+            let mut pip = RectangleShape::with_size(Vector2f::new(3., 3.));
+            pip.set_position(bottom_right - Vector2f::new(tile_size + 8. + index as f32 * 5., 5.));
+            pip.set_fill_color(BEAT_COLOR);
+            self.window.draw(&pip);
+        }
+    }
+
+    // the bag along the bottom of the view, followed by the equipped items
+    fn draw_inventory(&mut self) {
+        let Some(inventory) = &self.state.inventory else {
+            return;
+        };
+
+        let bottom_left = {
+            let view = self.window.view();
+            view.center() + Vector2f::new(-view.size().x, view.size().y) / 2.
+        };
+
+        let equipped = EQUIP_SLOTS.iter().map(|slot| {
+            (inventory.equipped.iter())
+                .find(|(equipped_slot, _)| equipped_slot == slot)
+                .map(|(_, item)| item)
+        });
+        let slots = inventory.bag.iter().map(Option::as_ref).chain(equipped);
+
+        let tile_size = TILE_SIZE as f32;
+        for (index, item) in slots.enumerate() {
+            let gap = if index < inventory.bag.len() {
+                0.
+            } else {
+                tile_size
+            };
+            let pos = bottom_left
+                + Vector2f::new(2. + index as f32 * (tile_size + 2.) + gap, -tile_size - 2.);
+
+            let mut frame = RectangleShape::with_size(Vector2f::new(tile_size, tile_size));
+            frame.set_position(pos);
+            frame.set_fill_color(Color::TRANSPARENT);
+            frame.set_outline_thickness(1.);
+            frame.set_outline_color(if index == self.selected_slot {
+                SELECTED_SLOT_COLOR
+            } else {
+                SLOT_COLOR
+            });
+            self.window.draw(&frame);
+
+            if let Some(item) = item
+                && let Some(Asset { texture }) = self.state.assets.get(&item.asset_id)
+            {
+                let mut sprite = Sprite::with_texture(texture);
+                sprite.set_position(pos);
+                self.window.draw(&sprite);
+            }
+        }
+    }
 }
 
 fn main() {
@@ -242,7 +673,7 @@ fn main() {
 
     let auth = {
         let username = fs::read_to_string("auth.txt").unwrap();
-        Auth { username }
+        Auth::new(username)
     };
 
     Client::new().run(auth, server_addr);
@@ -0,0 +1,17 @@
+use state::*;
+
+#[expect(clippy::allow_attributes)]
+#[allow(unused)]
+pub struct DecorationTypes {
+    pub barrel: DecorationTypeId,
+    pub chest: DecorationTypeId,
+}
+
+impl DecorationTypes {
+    pub fn new(state: &mut State) -> Self {
+        Self {
+            barrel: state.insert_type(DecorationType::new("barrel").solid()),
+            chest: state.insert_type(DecorationType::new("chest").solid().interactable()),
+        }
+    }
+}
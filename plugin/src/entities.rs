@@ -3,13 +3,25 @@ use state::*;
 #[expect(clippy::allow_attributes)]
 #[allow(unused)]
 pub struct EntityTypes {
+    pub arrow: EntityTypeId,
     pub skeleton: EntityTypeId,
 }
 
 impl EntityTypes {
     pub fn new(state: &mut State) -> Self {
         Self {
-            skeleton: state.insert_type(EntityType::new("skeleton")),
+            arrow: state.insert_type(EntityType::new("arrow")),
+            skeleton: state.insert_type(EntityType::new("skeleton").health(3).behaviour(
+                EveryNthBeat::new(
+                    2,
+                    ChaseNearestPlayer::new(8).or(Patrol::new([
+                        vec2(0, -2),
+                        vec2(-2, -2),
+                        vec2(-2, 0),
+                        vec2(0, 0),
+                    ])),
+                ),
+            )),
         }
     }
 }
@@ -0,0 +1,51 @@
+use crate::EntityTypes;
+use state::*;
+
+#[expect(clippy::allow_attributes)]
+#[allow(unused)]
+pub struct ItemTypes {
+    pub axe: ItemTypeId,
+    pub bow: ItemTypeId,
+    pub crossbow: ItemTypeId,
+    pub dagger: ItemTypeId,
+    pub hammer: ItemTypeId,
+    pub pickaxe: ItemTypeId,
+    pub saber: ItemTypeId,
+    pub shovel: ItemTypeId,
+    pub sword: ItemTypeId,
+    pub terrablade: ItemTypeId,
+    pub torch: ItemTypeId,
+}
+
+impl ItemTypes {
+    pub fn new(state: &mut State, entities: &EntityTypes) -> Self {
+        Self {
+            axe: state.insert_type(ItemType::new("axe").tool(ToolKind::Axe).damage(2)),
+            bow: state.insert_type(
+                ItemType::new("bow")
+                    .tool(ToolKind::Bow)
+                    .damage(1)
+                    .shoots(entities.arrow, 1),
+            ),
+            crossbow: state.insert_type(
+                ItemType::new("crossbow")
+                    .tool(ToolKind::Crossbow)
+                    .damage(2)
+                    .shoots(entities.arrow, 2),
+            ),
+            dagger: state.insert_type(ItemType::new("dagger").tool(ToolKind::Dagger).damage(1)),
+            hammer: state.insert_type(ItemType::new("hammer").tool(ToolKind::Hammer).damage(3)),
+            pickaxe: state.insert_type(ItemType::new("pickaxe").tool(ToolKind::Pickaxe).damage(1)),
+            saber: state.insert_type(ItemType::new("saber").tool(ToolKind::Sword).damage(2)),
+            shovel: state.insert_type(ItemType::new("shovel").tool(ToolKind::Shovel).damage(1)),
+            sword: state.insert_type(ItemType::new("sword").tool(ToolKind::Sword).damage(2)),
+            terrablade: state
+                .insert_type(ItemType::new("terrablade").tool(ToolKind::Sword).damage(4)),
+            torch: state.insert_type(
+                ItemType::new("torch")
+                    .equippable(EquipSlot::Torch)
+                    .light(5, 220),
+            ),
+        }
+    }
+}
@@ -4,56 +4,113 @@
 
 use state::*;
 
+mod decorations;
 mod entities;
 mod floors;
+mod items;
 mod walls;
 
+use decorations::*;
 use entities::*;
 use floors::*;
+use items::*;
 use walls::*;
 
+const SKINS: [&str; 3] = ["skins/suisei", "skins/ougi", "skins/mayuri"];
 #[expect(dead_code)]
 pub struct Plugin {
     floors: FloorTypes,
     walls: WallTypes,
+    decorations: DecorationTypes,
     entities: EntityTypes,
+    items: ItemTypes,
+    spawn_points: Vec<Vec2>,
 }
 
 impl Plugin {
     fn new(state: &mut State) -> Self {
         let floors = FloorTypes::new(state);
         let walls = WallTypes::new(state);
+        let decorations = DecorationTypes::new(state);
         let entities = EntityTypes::new(state);
+        let items = ItemTypes::new(state, &entities);
 
-        {
+        let spawn_points = {
             let min = vec2(-19, -12);
             let max = vec2(19, 12);
             let center = (min + max) / 2;
 
             state.place_rect(min, max, floors.grass.instance());
             state.place_frame(min, max, walls.bedrock.instance());
-
-            state.spawn(
-                PLAYER_ENTITY_TYPE_ID
-                    .instance(center - vec2(1, 0))
-                    .asset("skins/suisei"),
+            state.place_frame(
+                center + vec2(4, -2),
+                center + vec2(8, 2),
+                walls.planks.instance(),
             );
-            state.spawn(
-                PLAYER_ENTITY_TYPE_ID
-                    .instance(center + vec2(1, 0))
-                    .asset("skins/ougi"),
+            state.place(center + vec2(6, -2), walls.glass.instance());
+            state.place(center + vec2(6, 2), walls.glass.instance());
+
+            state.place(
+                center + vec2(7, -1),
+                decorations.chest.instance().loot_table(
+                    LootTable::new(2)
+                        .entry(items.hammer, 3)
+                        .entry(items.saber, 2)
+                        .entry(items.terrablade, 1)
+                        .nothing(2),
+                ),
             );
-        }
+            state.place(center + vec2(7, 1), decorations.barrel.instance());
+
+            match state.spawn(entities.skeleton.instance(center - vec2(4, 0))) {
+                Ok(skeleton) => {
+                    if let Err(error) = state.give_item(skeleton, items.dagger.instance()) {
+                        println!("error: {error:?}!");
+                    }
+                }
+
+                Err(error) => println!("error: {error:?}!"),
+            }
+
+            state.place(center + vec2(6, -1), items.pickaxe.instance());
+            state.place(center + vec2(6, 0), items.sword.instance());
+            state.place(center + vec2(6, 1), items.dagger.instance());
+            state.place(center + vec2(5, 0), items.torch.instance());
+            state.place(center + vec2(1, -3), items.bow.instance());
+            state.place(center + vec2(1, 3), items.crossbow.instance());
+
+            vec![center - vec2(1, 0), center + vec2(1, 0)]
+        };
 
         Self {
             floors,
             walls,
+            decorations,
             entities,
+            items,
+            spawn_points,
         }
     }
 
     #[unsafe(no_mangle)]
-    pub const extern "Rust" fn handle_event(&mut self, _state: &mut State) {}
+    pub const extern "Rust" fn handle_event(&mut self, _state: &mut State, _event: &PluginEvent) {}
+
+    #[unsafe(no_mangle)]
+    pub extern "Rust" fn spawn_player(
+        &mut self,
+        state: &State,
+        _username: &str,
+    ) -> Option<PlayerSpawn> {
+        let pos =
+            (self.spawn_points.iter().copied()).find(|&pos| state.entities_at(pos).is_empty())?;
+        let asset = SKINS[state.player_entity_ids().count() % SKINS.len()];
+
+        Some(PlayerSpawn {
+            pos,
+            asset: asset.to_owned(),
+            items: vec![self.items.axe.instance(), self.items.torch.instance()],
+        })
+    }
 }
 
 #[unsafe(no_mangle)]
@@ -4,6 +4,7 @@ use state::*;
 #[allow(unused)]
 pub struct WallTypes {
     pub bedrock: WallTypeId,
+    pub glass: WallTypeId,
     pub planks: WallTypeId,
 }
 
@@ -11,6 +12,7 @@ impl WallTypes {
     pub fn new(state: &mut State) -> Self {
         Self {
             bedrock: state.insert_type(WallType::new("bedrock")),
+            glass: state.insert_type(WallType::new("glass").shatters().transparent()),
             planks: state.insert_type(WallType::new("planks").breakable(ToolKind::Axe, 2)),
         }
     }
@@ -0,0 +1,261 @@
+use signals::{AssetData, AssetId};
+use state::{
+    Decoration, DecorationTypeId, Entity, EntityTypeId, Floor, FloorTypeId, Item, ItemTypeId,
+    ObjectType, PLAYER_ENTITY_TYPE_ID, State, Wall, WallTypeId,
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    fmt, fs,
+    hash::Hash,
+    io,
+    path::{Path, PathBuf},
+};
+
+const ASSET_EXTENSION: &str = "png";
+
+// isn't in any registry, so clients skip drawing it
+const MISSING_ASSET_ID: AssetId = AssetId(usize::MAX);
+
+fn floor_key(asset: &str) -> String {
+    format!("tiles/floors/{asset}")
+}
+
+fn wall_key(asset: &str) -> String {
+    format!("tiles/walls/{asset}")
+}
+
+fn decoration_key(asset: &str) -> String {
+    format!("tiles/decorations/{asset}")
+}
+
+fn item_key(asset: &str) -> String {
+    format!("items/{asset}")
+}
+
+fn entity_type_key(asset: &str) -> String {
+    format!("entities/{asset}")
+}
+
+// `Entity::asset` overrides are already relative to the assets root, e.g. "skins/suisei"
+fn entity_key(state: &State, entity: &Entity) -> String {
+    match &entity.asset {
+        Some(asset) => asset.clone(),
+        None => entity_type_key(&state.get_type(entity.type_id).asset),
+    }
+}
+
+#[derive(Debug)]
+pub struct MissingAssets(Vec<String>);
+
+impl fmt::Display for MissingAssets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "missing assets: {}", self.0.join(", "))
+    }
+}
+
+/// Every `.png` under the assets root, keyed by its relative path without the extension
+pub struct AssetRegistry {
+    root: PathBuf,
+    ids: HashMap<String, AssetId>,
+    data: Vec<(AssetId, AssetData)>,
+
+    // filled in by `check`, so that sending a tile doesn't have to build its key
+    floors: HashMap<FloorTypeId, AssetId>,
+    walls: HashMap<WallTypeId, AssetId>,
+    decorations: HashMap<DecorationTypeId, AssetId>,
+    entity_types: HashMap<EntityTypeId, AssetId>,
+    items: HashMap<ItemTypeId, AssetId>,
+
+    // keys that have already been logged as missing
+    reported: RefCell<HashSet<String>>,
+}
+
+impl AssetRegistry {
+    pub fn load(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+
+        let mut files = BTreeMap::default();
+        collect_files(&root, "", &mut files)?;
+
+        let mut ids = HashMap::default();
+        let mut data = Vec::default();
+        for (index, (key, bytes)) in files.into_iter().enumerate() {
+            let asset_id = AssetId(index);
+            ids.insert(key, asset_id);
+            data.push((asset_id, bytes));
+        }
+
+        Ok(Self {
+            root,
+            ids,
+            data,
+            floors: HashMap::default(),
+            walls: HashMap::default(),
+            decorations: HashMap::default(),
+            entity_types: HashMap::default(),
+            items: HashMap::default(),
+            reported: RefCell::default(),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Also resolves the asset of every type, types inserted later are looked up by their key
+    pub fn check(&mut self, state: &State) -> Result<(), MissingAssets> {
+        let mut missing = Vec::default();
+
+        self.floors = self.resolve(state.floor_types(), &mut missing, |floor_type| {
+            floor_key(&floor_type.asset)
+        });
+        self.walls = self.resolve(state.wall_types(), &mut missing, |wall_type| {
+            wall_key(&wall_type.asset)
+        });
+        self.decorations =
+            self.resolve(state.decoration_types(), &mut missing, |decoration_type| {
+                decoration_key(&decoration_type.asset)
+            });
+        self.entity_types = self.resolve(
+            (state.entity_types().iter()).filter(|&(&type_id, _)| type_id != PLAYER_ENTITY_TYPE_ID),
+            &mut missing,
+            |entity_type| entity_type_key(&entity_type.asset),
+        );
+        self.items = self.resolve(state.item_types(), &mut missing, |item_type| {
+            item_key(&item_type.asset)
+        });
+
+        missing.extend(
+            (state.entities().values())
+                .map(|entity| entity_key(state, entity))
+                .filter(|key| !self.ids.contains_key(key)),
+        );
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        missing.sort();
+        missing.dedup();
+        Err(MissingAssets(missing))
+    }
+
+    fn resolve<'a, TypeId: Copy + Eq + Hash + 'a, T: 'a>(
+        &self,
+        types: impl IntoIterator<Item = (&'a TypeId, &'a T)>,
+        missing: &mut Vec<String>,
+        key: impl Fn(&T) -> String,
+    ) -> HashMap<TypeId, AssetId> {
+        let mut resolved = HashMap::default();
+        for (&type_id, object_type) in types {
+            let key = key(object_type);
+            match self.ids.get(&key) {
+                Some(&asset_id) => {
+                    resolved.insert(type_id, asset_id);
+                }
+                None => missing.push(key),
+            }
+        }
+        resolved
+    }
+
+    /// For entities spawned after `check`
+    pub fn check_entity(&self, state: &State, entity: &Entity) -> Result<(), MissingAssets> {
+        let key = entity_key(state, entity);
+        if self.ids.contains_key(&key) {
+            Ok(())
+        } else {
+            Err(MissingAssets(vec![key]))
+        }
+    }
+
+    pub fn all(&self) -> Box<[(AssetId, AssetData)]> {
+        self.data.iter().cloned().collect()
+    }
+
+    pub fn floor_id(&self, state: &State, floor: &Floor) -> AssetId {
+        self.resolved(&self.floors, floor.type_id, || {
+            floor_key(&state.get_type(floor.type_id).asset)
+        })
+    }
+
+    pub fn wall_id(&self, state: &State, wall: &Wall) -> AssetId {
+        self.resolved(&self.walls, wall.type_id, || {
+            wall_key(&state.get_type(wall.type_id).asset)
+        })
+    }
+
+    pub fn decoration_id(&self, state: &State, decoration: &Decoration) -> AssetId {
+        self.resolved(&self.decorations, decoration.type_id, || {
+            decoration_key(&state.get_type(decoration.type_id).asset)
+        })
+    }
+
+    pub fn entity_id(&self, state: &State, entity: &Entity) -> AssetId {
+        match &entity.asset {
+            Some(asset) => self.id(asset),
+            None => self.resolved(&self.entity_types, entity.type_id, || {
+                entity_type_key(&state.get_type(entity.type_id).asset)
+            }),
+        }
+    }
+
+    pub fn item_id(&self, state: &State, item: &Item) -> AssetId {
+        self.resolved(&self.items, item.type_id, || {
+            item_key(&state.get_type(item.type_id).asset)
+        })
+    }
+
+    fn resolved<TypeId: Eq + Hash>(
+        &self,
+        resolved: &HashMap<TypeId, AssetId>,
+        type_id: TypeId,
+        key: impl FnOnce() -> String,
+    ) -> AssetId {
+        match resolved.get(&type_id) {
+            Some(&asset_id) => asset_id,
+            None => self.id(&key()),
+        }
+    }
+
+    // assets used after `check`, e.g. by entities spawned from plugin events, may be missing
+    fn id(&self, key: &str) -> AssetId {
+        if let Some(&asset_id) = self.ids.get(key) {
+            return asset_id;
+        }
+
+        if self.reported.borrow_mut().insert(key.to_owned()) {
+            println!("error: no asset for {}!", key);
+        }
+        MISSING_ASSET_ID
+    }
+}
+
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut BTreeMap<String, AssetData>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            if let Some(name) = path.file_name().and_then(OsStr::to_str) {
+                collect_files(&path, &format!("{prefix}{name}/"), files)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == ASSET_EXTENSION)
+            && let Some(name) = path.file_stem().and_then(OsStr::to_str)
+        {
+            files.insert(
+                format!("{prefix}{name}"),
+                fs::read(&path)?.into_boxed_slice(),
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,19 +1,57 @@
-use signals::PlayerSignal;
-use std::{io::BufReader, net::TcpStream};
+use signals::{
+    Auth, AuthReply, FrameError, FrameReader, FrameWriter, MAX_FRAME_SIZE, RejectReason,
+    ServerSignal, StampedSignal,
+};
+use std::net::TcpStream;
+
+// clients that fall this far behind are dropped instead of being buffered for forever
+const MAX_PENDING_SIZE: usize = 4 * MAX_FRAME_SIZE;
 
 pub struct Client {
-    stream: BufReader<TcpStream>,
+    stream: TcpStream,
+    reader: FrameReader,
+    writer: FrameWriter,
 }
 
 impl Client {
     pub fn new(stream: TcpStream) -> Self {
-        let stream = BufReader::new(stream);
-        Self { stream }
+        Self {
+            stream,
+            reader: FrameReader::default(),
+            writer: FrameWriter::default(),
+        }
     }
 }
 
 impl Client {
-    pub fn read_signal(&mut self) -> Result<PlayerSignal, bincode::Error> {
-        bincode::deserialize_from(&mut self.stream)
+    pub fn read_auth(&mut self) -> Result<Option<Result<Auth, RejectReason>>, FrameError> {
+        let frame = self.reader.read_frame(&mut self.stream)?;
+        Ok(frame.map(|frame| Auth::decode(&frame)))
+    }
+
+    pub fn read_signal(&mut self) -> Result<Option<StampedSignal>, FrameError> {
+        self.reader.read(&mut self.stream)
+    }
+
+    /// Only queues the reply, it's sent by `flush`
+    pub fn send_auth_reply(&mut self, reply: &AuthReply) -> Result<(), FrameError> {
+        self.writer.push(reply)
+    }
+
+    /// Only queues the signal, it's sent by `flush`
+    pub fn send_signal(&mut self, signal: &ServerSignal) -> Result<(), FrameError> {
+        self.writer.push(signal)
+    }
+
+    pub fn flush(&mut self) -> Result<(), FrameError> {
+        self.writer.flush(&mut self.stream)
+    }
+
+    pub const fn is_flushed(&self) -> bool {
+        self.writer.pending() == 0
+    }
+
+    pub const fn is_lagging(&self) -> bool {
+        self.writer.pending() > MAX_PENDING_SIZE
     }
 }
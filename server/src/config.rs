@@ -0,0 +1,96 @@
+use libloader::libloading::library_filename;
+use std::{env, fs, io, num::NonZeroU32, path::PathBuf, str::FromStr};
+
+const CONFIG_PATH: &str = "server.txt";
+const TARGET_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/release");
+const ASSETS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets");
+
+/// Read from `server.txt` (`key = value` lines), then overridden by `--key value` arguments
+pub struct Config {
+    pub plugin_path: PathBuf,
+    pub assets_path: PathBuf,
+    pub tick_rate: NonZeroU32,
+    // otherwise the entity is kept for the player to reclaim on rejoin
+    pub despawn_on_leave: bool,
+    pub bpm: NonZeroU32,
+    // in milliseconds to either side of a beat
+    pub beat_tolerance: u64,
+    // in milliseconds, how late after being sent an action may arrive and still be on beat
+    pub max_latency: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            plugin_path: PathBuf::from(TARGET_PATH).join(library_filename("plugin")),
+            assets_path: PathBuf::from(ASSETS_PATH),
+            tick_rate: NonZeroU32::new(20).unwrap(),
+            despawn_on_leave: false,
+            bpm: NonZeroU32::new(120).unwrap(),
+            beat_tolerance: 150,
+            max_latency: 150,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut config = Self::default();
+
+        match fs::read_to_string(CONFIG_PATH) {
+            Ok(text) => {
+                for line in text.lines().map(str::trim) {
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+
+                    let Some((key, value)) = line.split_once('=') else {
+                        panic!("invalid line in {CONFIG_PATH}: {line}");
+                    };
+                    config.set(key.trim(), value.trim());
+                }
+            }
+
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+
+            Err(error) => panic!("{}", error),
+        }
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let Some(key) = arg.strip_prefix("--") else {
+                panic!("unexpected argument: {arg}");
+            };
+
+            match key.split_once('=') {
+                Some((key, value)) => config.set(key, value),
+
+                None => {
+                    let value = args
+                        .next()
+                        .unwrap_or_else(|| panic!("no value for --{key}"));
+                    config.set(key, &value);
+                }
+            }
+        }
+
+        config
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "plugin" => self.plugin_path = PathBuf::from(value),
+            "assets" => self.assets_path = PathBuf::from(value),
+            "tick_rate" => self.tick_rate = parse(key, value),
+            "despawn_on_leave" => self.despawn_on_leave = parse(key, value),
+            "bpm" => self.bpm = parse(key, value),
+            "beat_tolerance" => self.beat_tolerance = parse(key, value),
+            "max_latency" => self.max_latency = parse(key, value),
+            _ => panic!("unknown config key: {key}"),
+        }
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> T {
+    (value.parse()).unwrap_or_else(|_| panic!("invalid value for {key}: {value}"))
+}
@@ -1,36 +1,43 @@
 //! Сервер должен только обрабатывать запросы клиентов, проверять их допустимость (верифицировать) и выполнять
 //! Допустимость проверяется через методы у `State`, у самого сервера их не должно быть
 
-use libloader::libloading::os::windows::Library;
-use std::{env, net::TcpListener};
+use std::net::TcpListener;
 
+mod assets;
 mod client;
+mod config;
 mod plugin;
 mod server;
 
+use assets::*;
 use client::*;
+use config::*;
 use plugin::*;
 use server::*;
 
-const PLUGIN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/release/plugin.dll");
-
 fn main() {
-    let library = unsafe { Library::new(PLUGIN_PATH).unwrap() };
+    let config = Config::load();
+
+    let assets = AssetRegistry::load(&config.assets_path).unwrap();
 
     let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
     listener.set_nonblocking(true).unwrap();
-    let mut server = Server::new(ServerConnector::new(listener), ServerUpdater::default());
+    let mut server = Server::new(
+        ServerConnector::new(listener, config.despawn_on_leave),
+        ServerUpdater::new(assets),
+        BeatClock::new(config.bpm, config.beat_tolerance, config.max_latency),
+    );
+
+    // FIXME call `init_field` manually later instead of passing state directly into `Plugin::load`
+    server.updater.load_plugin(&config.plugin_path).unwrap();
 
-    // FIXME call `init_field` manually later instead of passing state directly into `Plugin::new`
-    {
-        let plugin = Plugin::new(library, &mut server.updater.state).unwrap();
-        server.push_plugin(plugin);
+    if let Err(error) = server.updater.assets.check(&server.updater.state) {
+        panic!("{}", error);
     }
 
+    // the world the plugin has built is sent whole in `Authorized`, not as a delta
+    server.updater.state.take_changes();
+
     println!("server started!");
-    loop {
-        server.connector.try_auth_all(&server.updater.state);
-        server.connector.accept_all_unathorized();
-        server.connector.handle_clients(&mut server.updater);
-    }
+    server.run(config.tick_rate);
 }
@@ -1,38 +1,45 @@
-use libloader::libloading::{
-    Error as LibError,
-    os::windows::{Library, Symbol as LibSymbol},
-};
-use state::State;
-use std::ffi::c_void;
+use libloader::libloading::{Error as LibError, Library};
+use state::{PlayerSpawn, PluginEvent, State};
+use std::{ffi::c_void, path::Path};
 
 type PluginData = *mut c_void;
 
 pub struct Plugin {
-    pub plugin: PluginData,
-    pub uninit: LibSymbol<fn(PluginData)>,
-    pub handle_event: LibSymbol<fn(PluginData, &mut State)>,
+    plugin: PluginData,
+    uninit: fn(PluginData),
+    handle_event: fn(PluginData, &mut State, &PluginEvent),
+    spawn_player: fn(PluginData, &State, &str) -> Option<PlayerSpawn>,
+    // dropped after `uninit` is called, since the functions above point into it
+    _library: Library,
 }
 
 impl Plugin {
-    pub fn new(library: Library, state: &mut State) -> Result<Self, LibError> {
+    pub fn load(path: impl AsRef<Path>, state: &mut State) -> Result<Self, LibError> {
         unsafe {
-            let uninit = library.get(b"uninit")?;
+            let library = Library::new(path.as_ref())?;
+            let uninit = *library.get(b"uninit")?;
             let init = library.get::<fn(&mut State) -> _>(b"init")?;
-            let handle_event = library.get(b"handle_event")?;
+            let handle_event = *library.get(b"handle_event")?;
+            let spawn_player = *library.get(b"spawn_player")?;
 
             Ok(Self {
                 plugin: init(state),
                 handle_event,
+                spawn_player,
                 uninit,
+                _library: library,
             })
         }
     }
 }
 
 impl Plugin {
-    #[expect(dead_code)]
-    pub fn handle_event(&mut self, state: &mut State) {
-        (self.handle_event)(self.plugin, state);
+    pub fn handle_event(&mut self, state: &mut State, event: &PluginEvent) {
+        (self.handle_event)(self.plugin, state, event);
+    }
+
+    pub fn spawn_player(&mut self, state: &State, username: &str) -> Option<PlayerSpawn> {
+        (self.spawn_player)(self.plugin, state, username)
     }
 }
 
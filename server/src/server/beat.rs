@@ -0,0 +1,163 @@
+use std::{num::NonZeroU32, time::Instant};
+
+const BEATS_PER_MULTIPLIER: u32 = 4;
+const MAX_MULTIPLIER: u32 = 4;
+
+/// Beats happen every `60 / bpm` seconds since the server started, timestamps are milliseconds since then
+pub struct BeatClock {
+    start: Instant,
+    interval: u64,
+    // how far from a beat an action may be
+    tolerance: u64,
+    // how late after being sent an action may be received
+    max_latency: u64,
+    last_beat: u64,
+}
+
+impl BeatClock {
+    pub fn new(bpm: NonZeroU32, tolerance: u64, max_latency: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            interval: (60_000 / u64::from(bpm.get())).max(1),
+            tolerance,
+            max_latency,
+            last_beat: 0,
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        u64::try_from(self.start.elapsed().as_millis()).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the beat that has started since the last call, beats skipped by a slow tick aren't reported
+    pub fn tick(&mut self) -> Option<u64> {
+        let beat = self.now() / self.interval;
+        if beat <= self.last_beat {
+            return None;
+        }
+
+        self.last_beat = beat;
+        Some(beat)
+    }
+
+    pub const fn last_beat(&self) -> u64 {
+        self.last_beat
+    }
+
+    /// Returns the beat an action received now is within the tolerance window of, allowing for it to arrive late
+    pub fn beat_at(&self, timestamp: u64) -> Option<u64> {
+        self.judge(timestamp, self.now())
+    }
+
+    fn judge(&self, timestamp: u64, now: u64) -> Option<u64> {
+        // the stamp isn't trusted for judging, stamps far from the time of receiving are forged or out of sync
+        if timestamp.abs_diff(now) > self.max_latency {
+            return None;
+        }
+
+        // the latest beat whose window has started
+        let beat = (now + self.tolerance) / self.interval;
+        (now <= beat * self.interval + self.tolerance + self.max_latency).then_some(beat)
+    }
+
+    pub fn signal(&self, number: u64) -> signals::ServerSignal {
+        signals::ServerSignal::Beat(signals::Beat {
+            number,
+            timestamp: self.now(),
+            interval: self.interval,
+        })
+    }
+}
+
+/// Counts the consecutive beats a player has acted on
+#[derive(Default)]
+pub struct Groove {
+    streak: u32,
+    last_beat: Option<u64>,
+}
+
+impl Groove {
+    /// Returns whether the action is accepted, only one action per beat is
+    pub fn hit(&mut self, beat: Option<u64>) -> bool {
+        match beat {
+            Some(beat) if self.last_beat != Some(beat) => {
+                self.last_beat = Some(beat);
+                self.streak += 1;
+                true
+            }
+
+            _ => {
+                self.streak = 0;
+                false
+            }
+        }
+    }
+
+    /// Resets the streak if the beat before `beat` has passed without an action, returns whether it did
+    pub fn check_missed(&mut self, beat: u64) -> bool {
+        let missed =
+            self.streak > 0 && (self.last_beat).is_none_or(|last_beat| last_beat + 1 < beat);
+        if missed {
+            self.streak = 0;
+        }
+        missed
+    }
+
+    pub fn signal(&self) -> signals::ServerSignal {
+        signals::ServerSignal::Groove(signals::Groove {
+            streak: self.streak,
+            multiplier: (1 + self.streak / BEATS_PER_MULTIPLIER).min(MAX_MULTIPLIER),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // beats every 500ms, with beat 4 at 2000
+    fn clock() -> BeatClock {
+        BeatClock::new(NonZeroU32::new(120).unwrap(), 100, 50)
+    }
+
+    #[test]
+    fn the_window_spans_the_tolerance_and_latency() {
+        let clock = clock();
+        assert_eq!(clock.judge(1900, 1900), Some(4));
+        assert_eq!(clock.judge(1899, 1899), None);
+        assert_eq!(clock.judge(2150, 2150), Some(4));
+        assert_eq!(clock.judge(2151, 2151), None);
+    }
+
+    #[test]
+    fn stamps_far_from_receiving_are_refused() {
+        let clock = clock();
+        assert_eq!(clock.judge(1950, 2000), Some(4));
+        assert_eq!(clock.judge(1949, 2000), None);
+        assert_eq!(clock.judge(2051, 2000), None);
+        assert_eq!(clock.judge(0, 2000), None);
+    }
+
+    #[test]
+    fn only_one_action_per_beat_is_accepted() {
+        let mut groove = Groove::default();
+        assert!(groove.hit(Some(4)));
+        assert!(groove.hit(Some(5)));
+        assert_eq!(groove.streak, 2);
+
+        assert!(!groove.hit(Some(5)));
+        assert_eq!(groove.streak, 0);
+        assert!(!groove.hit(None));
+    }
+
+    #[test]
+    fn missed_beats_are_reported_once() {
+        let mut groove = Groove::default();
+        groove.hit(Some(4));
+
+        assert!(!groove.check_missed(5));
+        assert!(groove.check_missed(6));
+        assert_eq!(groove.streak, 0);
+        assert!(!groove.check_missed(7));
+    }
+}
@@ -1,219 +1,772 @@
-use super::ServerUpdater;
-use crate::Client;
-use state::{EntityId, ObjectType, State};
+use super::{BeatClock, Groove, PlayerView, ServerUpdater};
+use crate::{AssetRegistry, Client};
+use signals::{FrameError, PlayerSignal};
+use state::{
+    Changes, Decoration, Entity, EntityId, EquipSlot, Floor, Item, ObjectType, PluginEvent, State,
+    Wall,
+};
 use std::{
-    io,
     mem::take,
-    net::{TcpListener, TcpStream},
+    net::TcpListener,
+    time::{Duration, Instant},
 };
 
-fn make_init_signal(state: &State) -> signals::ClientUpdate {
-    // FIXME
-    let grass_asset_id = signals::AssetId(0);
-    let bedrock_asset_id = signals::AssetId(1);
-    let suisei_asset_id = signals::AssetId(2);
-    let ougi_asset_id = signals::AssetId(3);
-
-    let asset_to_id = |asset: &str| match asset {
-        "grass" => grass_asset_id,
-        "bedrock" => bedrock_asset_id,
-        "skins/suisei" => suisei_asset_id,
-        "skins/ougi" => ougi_asset_id,
-        _ => panic!("no asset for {asset}"),
+pub(super) fn make_floor(assets: &AssetRegistry, state: &State, floor: &Floor) -> signals::Tile {
+    let asset_id = assets.floor_id(state, floor);
+    signals::Tile { asset_id }
+}
+
+pub(super) fn make_wall(assets: &AssetRegistry, state: &State, wall: &Wall) -> signals::WallTile {
+    let asset_id = assets.wall_id(state, wall);
+
+    let damage_stage = match state.get_type(wall.type_id).breakable {
+        Some(breakable) => {
+            // walls with no hits to take break on the first one, so they never show damage
+            let stage = (wall.damage * usize::from(signals::DAMAGE_STAGES))
+                .checked_div(breakable.hits)
+                .unwrap_or(0);
+            u8::try_from(stage).map_or(signals::DAMAGE_STAGES - 1, |stage| {
+                stage.min(signals::DAMAGE_STAGES - 1)
+            })
+        }
+        None => 0,
     };
 
-    let assets = Box::new([
-        (
-            grass_asset_id,
-            Box::new(*include_bytes!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/../assets/tiles/floors/grass.png"
-            ))) as _,
-        ),
-        (
-            bedrock_asset_id,
-            Box::new(*include_bytes!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/../assets/tiles/walls/bedrock.png"
-            ))) as _,
-        ),
-        (
-            suisei_asset_id,
-            Box::new(*include_bytes!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/../assets/skins/suisei.png"
-            ))) as _,
-        ),
-        (
-            ougi_asset_id,
-            Box::new(*include_bytes!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/../assets/skins/ougi.png"
-            ))) as _,
-        ),
-    ]);
-
-    let floors = state
-        .floors()
-        .iter()
-        .map(|(&(x, y), floor)| {
-            let asset_id = asset_to_id(&state.get_type(floor.type_id).asset);
-
-            let pos = signals::Position::new(x, y);
-            let tile = signals::Tile { asset_id };
-            (pos, tile)
+    signals::WallTile {
+        asset_id,
+        damage_stage,
+    }
+}
+
+pub(super) fn make_decoration(
+    assets: &AssetRegistry,
+    state: &State,
+    decoration: &Decoration,
+) -> signals::Tile {
+    let asset_id = assets.decoration_id(state, decoration);
+    signals::Tile { asset_id }
+}
+
+pub(super) const fn make_entity_id(entity_id: EntityId) -> signals::EntityId {
+    signals::EntityId {
+        index: entity_id.index(),
+        generation: entity_id.generation(),
+    }
+}
+
+pub(super) fn make_entity(
+    assets: &AssetRegistry,
+    state: &State,
+    entity: &Entity,
+) -> signals::Entity {
+    let asset_id = assets.entity_id(state, entity);
+
+    signals::Entity {
+        asset_id,
+        pos: signals::Position {
+            x: entity.pos.x,
+            y: entity.pos.y,
+        },
+        health: (entity.health).map(|health| signals::Health {
+            current: health.current,
+            max: health.max,
+        }),
+    }
+}
+
+fn make_item(assets: &AssetRegistry, state: &State, item: &Item) -> signals::Item {
+    let asset_id = assets.item_id(state, item);
+    signals::Item { asset_id }
+}
+
+pub(super) fn make_ground_item(
+    assets: &AssetRegistry,
+    state: &State,
+    items: &[Item],
+) -> Option<signals::Tile> {
+    let asset_id = assets.item_id(state, items.last()?);
+    Some(signals::Tile { asset_id })
+}
+
+const fn make_equip_slot(slot: EquipSlot) -> signals::EquipSlot {
+    match slot {
+        EquipSlot::Weapon => signals::EquipSlot::Weapon,
+        EquipSlot::DiggingTool => signals::EquipSlot::DiggingTool,
+        EquipSlot::Torch => signals::EquipSlot::Torch,
+    }
+}
+
+fn make_inventory_signal(
+    assets: &AssetRegistry,
+    state: &State,
+    entity_id: EntityId,
+) -> Option<signals::ServerSignal> {
+    let inventory = state.inventory(entity_id).ok()?;
+
+    let bag = (inventory.bag.iter())
+        .map(|item| item.as_ref().map(|item| make_item(assets, state, item)))
+        .collect();
+
+    let equipped = (inventory.equipped.iter())
+        .map(|(&slot, item)| (make_equip_slot(slot), make_item(assets, state, item)))
+        .collect();
+
+    Some(signals::ServerSignal::Inventory(signals::Inventory {
+        bag,
+        equipped,
+    }))
+}
+
+fn send_inventory(client: &mut Client, updater: &ServerUpdater, entity_id: EntityId) {
+    let Some(signal) = make_inventory_signal(&updater.assets, &updater.state, entity_id) else {
+        return;
+    };
+
+    if let Err(error) = client.send_signal(&signal) {
+        println!("error: {:?}!", error);
+    }
+}
+
+fn make_init_signal(assets: &AssetRegistry, state: &State) -> signals::ClientUpdate {
+    let floors = (state.floors().iter())
+        .map(|((x, y), floor)| {
+            (
+                signals::Position::new(x, y),
+                Some(make_floor(assets, state, floor)),
+            )
         })
         .collect();
 
-    let walls = state
-        .walls()
-        .iter()
-        .map(|(&(x, y), wall)| {
-            let asset_id = asset_to_id(&state.get_type(wall.type_id).asset);
+    let walls = (state.walls().iter())
+        .map(|((x, y), wall)| {
+            (
+                signals::Position::new(x, y),
+                Some(make_wall(assets, state, wall)),
+            )
+        })
+        .collect();
 
-            let pos = signals::Position::new(x, y);
-            let tile = signals::Tile { asset_id };
-            (pos, tile)
+    let decorations = (state.decorations().iter())
+        .map(|(&(x, y), decoration)| {
+            (
+                signals::Position::new(x, y),
+                Some(make_decoration(assets, state, decoration)),
+            )
         })
         .collect();
 
-    let entities = state
-        .entities()
-        .iter()
+    let entities = (state.entities().iter())
         .map(|(&entity_id, entity)| {
-            let asset =
-                (entity.asset.as_ref()).unwrap_or_else(|| &state.get_type(entity.type_id).asset);
-            let asset_id = asset_to_id(asset);
-
-            (signals::EntityId(entity_id.into()), signals::Entity {
-                asset_id,
-                pos: signals::Position {
-                    x: entity.pos.x,
-                    y: entity.pos.y,
-                },
-            })
+            (
+                make_entity_id(entity_id),
+                Some(make_entity(assets, state, entity)),
+            )
+        })
+        .collect();
+
+    let items = (state.items().iter())
+        .map(|(&(x, y), items)| {
+            (
+                signals::Position::new(x, y),
+                make_ground_item(assets, state, items),
+            )
         })
         .collect();
 
     signals::ClientUpdate {
-        assets,
+        assets: assets.all(),
         floors,
         walls,
+        decorations,
         entities,
+        items,
+        stale: Box::default(),
+        light: Box::default(),
+    }
+}
+
+fn make_delta_signal(
+    assets: &AssetRegistry,
+    state: &State,
+    changes: &Changes,
+) -> signals::ClientUpdate {
+    let floors = (changes.floors.iter())
+        .map(|&(x, y)| {
+            let floor = state.floors().get(&(x, y));
+            (
+                signals::Position::new(x, y),
+                floor.map(|floor| make_floor(assets, state, floor)),
+            )
+        })
+        .collect();
+
+    let walls = (changes.walls.iter())
+        .map(|&(x, y)| {
+            let wall = state.walls().get(&(x, y));
+            (
+                signals::Position::new(x, y),
+                wall.map(|wall| make_wall(assets, state, wall)),
+            )
+        })
+        .collect();
+
+    let decorations = (changes.decorations.iter())
+        .map(|&(x, y)| {
+            let decoration = state.decorations().get(&(x, y));
+            (
+                signals::Position::new(x, y),
+                decoration.map(|decoration| make_decoration(assets, state, decoration)),
+            )
+        })
+        .collect();
+
+    let entities = (changes.entities.iter())
+        .map(|&entity_id| {
+            let entity = state.entities().get(&entity_id);
+            (
+                make_entity_id(entity_id),
+                entity.map(|entity| make_entity(assets, state, entity)),
+            )
+        })
+        .collect();
+
+    let items = (changes.items.iter())
+        .map(|&(x, y)| {
+            let items = state.items().get(&(x, y));
+            (
+                signals::Position::new(x, y),
+                items.and_then(|items| make_ground_item(assets, state, items)),
+            )
+        })
+        .collect();
+
+    signals::ClientUpdate {
+        assets: Box::default(),
+        floors,
+        walls,
+        decorations,
+        entities,
+        items,
+        stale: Box::default(),
+        light: Box::default(),
+    }
+}
+
+// spectators see everything, players only what's in their view
+fn make_authorized(
+    updater: &ServerUpdater,
+    entity_id: Option<EntityId>,
+    view: &mut PlayerView,
+) -> signals::Authorized {
+    let light = updater.state.light_map();
+    let update = match entity_id {
+        Some(entity_id) => view.update(&updater.assets, &updater.state, &light, entity_id, None),
+
+        None => signals::ClientUpdate {
+            light: view.update_spectator_light(&light),
+            ..make_init_signal(&updater.assets, &updater.state)
+        },
+    };
+
+    signals::Authorized {
+        player_entity_id: entity_id.map(make_entity_id),
+        update,
+    }
+}
+
+// everything else, like managing the inventory, can be done between beats
+const fn is_rhythmic(signal: &PlayerSignal) -> bool {
+    matches!(
+        signal,
+        PlayerSignal::Move(_)
+            | PlayerSignal::Dig(_)
+            | PlayerSignal::Interact(_)
+            | PlayerSignal::Attack(_)
+    )
+}
+
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct UnauthorizedClient {
+    client: Client,
+    connected_at: Instant,
+}
+
+// how long a rejected client is kept for the reason to be sent
+const REJECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct RejectedClient {
+    client: Client,
+    rejected_at: Instant,
+}
+
+impl UnauthorizedClient {
+    fn reject(mut self, reason: signals::RejectReason) -> Option<RejectedClient> {
+        println!("rejected: {}!", reason);
+
+        let reply = signals::AuthReply::Rejected { reason };
+        if let Err(error) = self.client.send_auth_reply(&reply) {
+            println!("error: {:?}!", error);
+            return None;
+        }
+
+        Some(RejectedClient {
+            client: self.client,
+            rejected_at: Instant::now(),
+        })
+    }
+}
+
+fn print_frame_error(error: &FrameError) {
+    match error {
+        FrameError::Closed => println!("disconnected!"),
+        error => println!("error: {:?}!", error),
+    }
+}
+
+fn disconnect(player: &mut Player, updater: &mut ServerUpdater, despawn_on_leave: bool) {
+    player.client = None;
+    if let Some(entity_id) = player.entity_id {
+        updater.state.push_event(PluginEvent::PlayerLeft {
+            entity_id,
+            username: player.username.clone(),
+        });
+
+        if despawn_on_leave {
+            updater.state.despawn(entity_id);
+            player.entity_id = None;
+        }
     }
 }
 
 struct Player {
     username: String,
     client: Option<Client>,
-    entity_id: EntityId,
+    // `None` for spectators, kept after disconnecting so that the entity can be reclaimed on rejoin
+    entity_id: Option<EntityId>,
+    groove: Groove,
+    view: PlayerView,
 }
 
 pub struct ServerConnector {
     listener: TcpListener,
-    unauthorized_clients: Vec<TcpStream>,
+    unauthorized_clients: Vec<UnauthorizedClient>,
+    // dropped once their rejection has been sent
+    rejected_clients: Vec<RejectedClient>,
     // FIXME `HashMap<EntityId, Player \ entity_id>` or `BTreeSet<String, Player \ username>`?
     players: Vec<Player>,
+    despawn_on_leave: bool,
 }
 
 impl ServerConnector {
-    pub fn new(listener: TcpListener) -> Self {
+    pub fn new(listener: TcpListener, despawn_on_leave: bool) -> Self {
         Self {
             listener,
             unauthorized_clients: Vec::default(),
+            rejected_clients: Vec::default(),
             players: Vec::default(),
+            despawn_on_leave,
         }
     }
 
     pub fn accept_all_unathorized(&mut self) {
         while let Ok((stream, _)) = self.listener.accept() {
-            self.unauthorized_clients.push(stream);
+            // accepted streams only inherit non-blocking mode on some platforms
+            match stream.set_nonblocking(true) {
+                Ok(()) => self.unauthorized_clients.push(UnauthorizedClient {
+                    client: Client::new(stream),
+                    connected_at: Instant::now(),
+                }),
+                Err(error) => println!("error: {:?}!", error),
+            }
+        }
+    }
+
+    pub fn try_auth_all(&mut self, updater: &mut ServerUpdater, clock: &BeatClock) {
+        for mut unauthorized in take(&mut self.unauthorized_clients) {
+            match unauthorized.client.read_auth() {
+                Ok(Some(Ok(signals::Auth { username, .. }))) => {
+                    self.authorize(unauthorized, username, updater, clock);
+                }
+
+                Ok(Some(Err(reason))) => self.rejected_clients.extend(unauthorized.reject(reason)),
+
+                Ok(None) if unauthorized.connected_at.elapsed() > AUTH_TIMEOUT => {
+                    let rejected = unauthorized.reject(signals::RejectReason::Timeout);
+                    self.rejected_clients.extend(rejected);
+                }
+
+                Ok(None) => self.unauthorized_clients.push(unauthorized),
+
+                Err(error) => println!("error: {:?}!", error),
+            }
         }
     }
 
-    pub fn try_auth_all(&mut self, state: &State) {
-        self.unauthorized_clients = take(&mut self.unauthorized_clients)
-            .into_iter()
-            .filter_map(|mut stream| match bincode::deserialize_from(&mut stream) {
-                Ok(signals::Auth { username }) => {
-                    let entity_id = self.get_or_pick_entity(&username, state);
+    fn authorize(
+        &mut self,
+        unauthorized: UnauthorizedClient,
+        username: String,
+        updater: &mut ServerUpdater,
+        clock: &BeatClock,
+    ) {
+        let index = (self.players.iter()).position(|player| player.username == username);
+        let previous_entity_id = match index.map(|index| &self.players[index]) {
+            Some(Player {
+                client: Some(_), ..
+            }) => {
+                let rejected = unauthorized.reject(signals::RejectReason::AlreadyConnected);
+                self.rejected_clients.extend(rejected);
+                return;
+            }
+
+            Some(player) => player.entity_id,
+            None => None,
+        };
+
+        let mut client = unauthorized.client;
+        let picked = self.pick_entity(previous_entity_id, &updater.state);
+        let spawned = picked.map_or_else(|| updater.spawn_player(&username), |_| None);
+        let entity_id = (picked.or(spawned)).or_else(|| self.take_reserved_entity(&updater.state));
+        let mut view = PlayerView::default();
+
+        {
+            let reply =
+                signals::AuthReply::Authorized(make_authorized(updater, entity_id, &mut view));
+            if let Err(error) = client.send_auth_reply(&reply) {
+                println!("error: {:?}!", error);
+
+                // nobody would get to play as it
+                if let Some(entity_id) = spawned {
+                    updater.state.despawn(entity_id);
+                }
+                return;
+            }
+        }
+
+        // actions are stamped with the server time estimated from the last beat
+        if let Err(error) = client.send_signal(&clock.signal(clock.last_beat())) {
+            println!("error: {:?}!", error);
+        }
+
+        if let Some(entity_id) = entity_id {
+            send_inventory(&mut client, updater, entity_id);
+        }
+
+        match entity_id {
+            Some(entity_id) => {
+                println!("{} (re)joined as {:?}!", username, entity_id);
+                updater.state.push_event(PluginEvent::PlayerJoined {
+                    entity_id,
+                    username: username.clone(),
+                });
+            }
+
+            None => println!("{} joined as a spectator!", username),
+        }
+
+        let player = Player {
+            username,
+            client: Some(client),
+            entity_id,
+            groove: Groove::default(),
+            view,
+        };
+        match index {
+            Some(index) => self.players[index] = player,
+            None => self.players.push(player),
+        }
+    }
+
+    pub fn handle_clients(&mut self, updater: &mut ServerUpdater, clock: &BeatClock) {
+        let mut reload_reply = None;
+
+        'players: for player in &mut self.players {
+            let Some(client) = &mut player.client else {
+                continue;
+            };
 
-                    {
-                        let update = make_init_signal(state);
-                        bincode::serialize_into(&mut stream, &signals::Authorized {
-                            player_entity_id: signals::EntityId(entity_id.into()),
-                            update,
-                        })
-                        .ok()?;
+            loop {
+                match client.read_signal() {
+                    Ok(Some(signals::StampedSignal { timestamp, signal })) => {
+                        let maybe_reply = match player.entity_id {
+                            Some(entity_id) if is_rhythmic(&signal) => {
+                                let on_beat = player.groove.hit(clock.beat_at(timestamp));
+                                if let Err(error) = client.send_signal(&player.groove.signal()) {
+                                    println!("error: {:?}!", error);
+                                }
+
+                                if on_beat {
+                                    updater.handle_signal(entity_id, signal)
+                                } else {
+                                    Some(signals::ServerSignal::OffBeat)
+                                }
+                            }
+
+                            Some(entity_id) => updater.handle_signal(entity_id, signal),
+                            None => updater.handle_spectator_signal(signal),
+                        };
+
+                        // signals left over are handled after the resync, with the entities of the new `State`
+                        if updater.reloaded() {
+                            // sent after the resync, which clears the client's log
+                            reload_reply =
+                                maybe_reply.map(|reply| (player.username.clone(), reply));
+                            break 'players;
+                        }
+
+                        if let Some(reply) = maybe_reply
+                            && let Err(error) = client.send_signal(&reply)
+                        {
+                            println!("error: {:?}!", error);
+                        }
                     }
 
-                    println!("{} (re)joined as {:?}!", username, entity_id);
+                    Ok(None) => break,
 
-                    self.players.push(Player {
-                        username,
-                        client: Some(Client::new(stream)),
-                        entity_id,
-                    });
-                    None
+                    // the stream can't be resynchronized after a broken frame
+                    Err(error) => {
+                        print_frame_error(&error);
+                        disconnect(player, updater, self.despawn_on_leave);
+                        break;
+                    }
                 }
+            }
+        }
 
-                Err(_) => Some(stream),
-            })
-            .collect();
+        self.forget_left();
+        if updater.take_reloaded() {
+            self.resync_all(updater);
+        }
+
+        if let Some((username, reply)) = reload_reply
+            && let Some(client) = (self.players.iter_mut())
+                .find(|player| player.username == username)
+                .and_then(|player| player.client.as_mut())
+            && let Err(error) = client.send_signal(&reply)
+        {
+            println!("error: {:?}!", error);
+        }
+    }
+
+    /// Hands player entities freed by disconnected players (or newly spawned ones) to spectators in the order they joined
+    pub fn assign_spectators(&mut self, updater: &mut ServerUpdater) {
+        for index in 0..self.players.len() {
+            if self.players[index].client.is_none() || self.players[index].entity_id.is_some() {
+                continue;
+            }
+
+            let username = self.players[index].username.clone();
+            let Some(entity_id) = self.claim_entity(None, &username, updater) else {
+                break;
+            };
+
+            let player = &mut self.players[index];
+            player.entity_id = Some(entity_id);
+            println!("{} took {:?}!", player.username, entity_id);
+
+            // the client has to forget the whole map it has seen as a spectator
+            player.view = PlayerView::default();
+            if let Some(client) = &mut player.client {
+                let signal = signals::ServerSignal::Resync(make_authorized(
+                    updater,
+                    Some(entity_id),
+                    &mut player.view,
+                ));
+                if let Err(error) = client.send_signal(&signal) {
+                    println!("error: {:?}!", error);
+                }
+
+                send_inventory(client, updater, entity_id);
+            }
+
+            updater.state.push_event(PluginEvent::PlayerJoined {
+                entity_id,
+                username: player.username.clone(),
+            });
+        }
     }
 
-    pub fn handle_clients(&mut self, updater: &mut ServerUpdater) {
+    /// Sends the beat to everyone and resets the grooves of players who've missed the previous one
+    pub fn beat(&mut self, clock: &BeatClock, number: u64) {
+        let signal = clock.signal(number);
+
         for player in &mut self.players {
             let Some(client) = &mut player.client else {
                 continue;
             };
 
-            match client.read_signal() {
-                Ok(signal) => {
-                    updater.handle_signal(signal);
-                }
+            if let Err(error) = client.send_signal(&signal) {
+                println!("error: {:?}!", error);
+            }
+
+            if player.entity_id.is_some()
+                && player.groove.check_missed(number)
+                && let Err(error) = client.send_signal(&player.groove.signal())
+            {
+                println!("error: {:?}!", error);
+            }
+        }
+    }
+
+    fn resync_all(&mut self, updater: &mut ServerUpdater) {
+        // entities of the previous `State` are gone along with the slots of disconnected players
+        let players = take(&mut self.players);
+
+        for mut player in players {
+            let Some(client) = &mut player.client else {
+                continue;
+            };
+
+            player.entity_id = self.claim_entity(None, &player.username, updater);
+            player.view = PlayerView::default();
+
+            let signal = signals::ServerSignal::Resync(make_authorized(
+                updater,
+                player.entity_id,
+                &mut player.view,
+            ));
+            if let Err(error) = client.send_signal(&signal) {
+                println!("error: {:?}!", error);
+            }
+
+            if let Some(entity_id) = player.entity_id {
+                send_inventory(client, updater, entity_id);
+                updater.state.push_event(PluginEvent::PlayerJoined {
+                    entity_id,
+                    username: player.username.clone(),
+                });
+            }
+            self.players.push(player);
+        }
+
+        // already sent as part of the resync
+        updater.state.take_changes();
+    }
+
+    pub fn send_changes(&mut self, updater: &mut ServerUpdater) {
+        let changes = updater.state.take_changes();
+        if changes.is_empty() {
+            return;
+        }
+
+        let light = updater.state.light_map();
+        for player in &mut self.players {
+            let Some(client) = &mut player.client else {
+                continue;
+            };
 
-                Err(error) => match *error {
-                    bincode::ErrorKind::Io(error) if error.kind() == io::ErrorKind::WouldBlock => {}
-
-                    bincode::ErrorKind::Io(error)
-                        if matches!(
-                            error.kind(),
-                            io::ErrorKind::ConnectionReset
-                                | io::ErrorKind::ConnectionAborted
-                                | io::ErrorKind::UnexpectedEof
-                        ) =>
-                    {
-                        println!("disconnected!");
-                        player.client = None;
+            let result = match player.entity_id {
+                Some(entity_id) => {
+                    let update = (player.view).update(
+                        &updater.assets,
+                        &updater.state,
+                        &light,
+                        entity_id,
+                        Some(&changes),
+                    );
+                    if update.is_empty() {
+                        Ok(())
+                    } else {
+                        client.send_signal(&signals::ServerSignal::Update(update))
                     }
+                }
 
-                    _ => println!("error: {:?}!", error),
-                },
+                None => client.send_signal(&signals::ServerSignal::Update(signals::ClientUpdate {
+                    light: player.view.update_spectator_light(&light),
+                    ..make_delta_signal(&updater.assets, &updater.state, &changes)
+                })),
+            };
+            if let Err(error) = result {
+                println!("error: {:?}!", error);
+            }
+
+            if let Some(entity_id) = player.entity_id
+                && changes.inventories.contains(&entity_id)
+            {
+                send_inventory(client, updater, entity_id);
             }
         }
     }
 
-    fn get_or_pick_entity(&self, username: &str, state: &State) -> EntityId {
-        if let Some(entity_id) = self.get_player_entity(username) {
-            return entity_id;
+    /// Sends what's been queued for every client, dropping those who can't keep up
+    pub fn flush_all(&mut self, updater: &mut ServerUpdater) {
+        for player in &mut self.players {
+            let Some(client) = &mut player.client else {
+                continue;
+            };
+
+            match client.flush() {
+                Ok(()) if client.is_lagging() => println!("{} is too far behind!", player.username),
+                Ok(()) => continue,
+                Err(error) => print_frame_error(&error),
+            }
+            disconnect(player, updater, self.despawn_on_leave);
         }
 
-        let mut free_entity_ids = state.player_entity_ids().filter(move |&player_entity_id| {
-            self.players
-                .iter()
-                .all(move |player| player.entity_id != player_entity_id)
-        });
+        self.forget_left();
+
+        self.rejected_clients
+            .retain_mut(|rejected| match rejected.client.flush() {
+                Ok(()) => {
+                    !rejected.client.is_flushed()
+                        && rejected.rejected_at.elapsed() < REJECTION_TIMEOUT
+                }
+
+                Err(error) => {
+                    print_frame_error(&error);
+                    false
+                }
+            });
+    }
 
-        // FIXME remove `expect`
-        free_entity_ids
-            .next()
-            .expect("no player entities to assign")
+    // players who've left without an entity to reclaim on rejoin have nothing to keep
+    fn forget_left(&mut self) {
+        (self.players).retain(|player| player.client.is_some() || player.entity_id.is_some());
+    }
+
+    fn claim_entity(
+        &mut self,
+        preferred: Option<EntityId>,
+        username: &str,
+        updater: &mut ServerUpdater,
+    ) -> Option<EntityId> {
+        (self.pick_entity(preferred, &updater.state))
+            .or_else(|| updater.spawn_player(username))
+            .or_else(|| self.take_reserved_entity(&updater.state))
+    }
+
+    /// Prefers `preferred`, then entities nobody has played as
+    fn pick_entity(&self, preferred: Option<EntityId>, state: &State) -> Option<EntityId> {
+        let is_taken = |entity_id| {
+            (self.players.iter())
+                .any(|player| player.client.is_some() && player.entity_id == Some(entity_id))
+        };
+
+        let is_reserved = |entity_id| {
+            (self.players.iter())
+                .any(|player| player.client.is_none() && player.entity_id == Some(entity_id))
+        };
+
+        if let Some(entity_id) = preferred
+            && state.entities().contains_key(&entity_id)
+            && !is_taken(entity_id)
+        {
+            return Some(entity_id);
+        }
+
+        (state.player_entity_ids()).find(|&entity_id| {
+            state.is_alive(entity_id) && !is_taken(entity_id) && !is_reserved(entity_id)
+        })
     }
 
-    fn get_player_entity(&self, username: &str) -> Option<EntityId> {
-        self.players
-            .iter()
-            .find_map(|player| (player.username == username).then_some(player.entity_id))
+    /// Only once no entity can be spawned, the entity of a disconnected player is handed over for good
+    fn take_reserved_entity(&mut self, state: &State) -> Option<EntityId> {
+        // corpses are only reclaimed by their own players
+        let player = (self.players.iter_mut()).find(|player| {
+            player.client.is_none()
+                && (player.entity_id).is_some_and(|entity_id| state.is_alive(entity_id))
+        })?;
+        player.entity_id.take()
     }
 }
@@ -1,23 +1,70 @@
-use crate::Plugin;
+use std::{
+    num::NonZeroU32,
+    thread,
+    time::{Duration, Instant},
+};
 
+mod beat;
 mod connector;
 mod updater;
+mod view;
 
+pub use beat::*;
 pub use connector::*;
 pub use updater::*;
+pub use view::*;
 
 // FIXME `pub(crate)`s
 pub struct Server {
     pub(crate) connector: ServerConnector,
     pub(crate) updater: ServerUpdater,
+    clock: BeatClock,
 }
 
 impl Server {
-    pub fn new(connector: ServerConnector, updater: ServerUpdater) -> Self {
-        Self { connector, updater }
+    pub fn new(connector: ServerConnector, updater: ServerUpdater, clock: BeatClock) -> Self {
+        Self {
+            connector,
+            updater,
+            clock,
+        }
     }
+}
+
+impl Server {
+    pub fn tick(&mut self) {
+        self.connector.accept_all_unathorized();
+        self.connector.try_auth_all(&mut self.updater, &self.clock);
+        self.connector
+            .handle_clients(&mut self.updater, &self.clock);
+        self.connector.assign_spectators(&mut self.updater);
+
+        if let Some(number) = self.clock.tick() {
+            self.connector.beat(&self.clock, number);
+            self.updater.beat(number);
+        }
+
+        self.updater.tick();
+
+        self.connector.send_changes(&mut self.updater);
+        self.connector.flush_all(&mut self.updater);
+    }
+
+    pub fn run(&mut self, tick_rate: NonZeroU32) -> ! {
+        let tick_duration = Duration::from_secs(1) / tick_rate.get();
+        let mut next_tick = Instant::now();
+
+        loop {
+            self.tick();
 
-    pub fn push_plugin(&mut self, plugin: Plugin) {
-        self.updater.plugins.push(plugin);
+            next_tick += tick_duration;
+            let now = Instant::now();
+            if next_tick > now {
+                thread::sleep(next_tick - now);
+            } else {
+                // don't try to catch up after a slow tick
+                next_tick = now;
+            }
+        }
     }
 }
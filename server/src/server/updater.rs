@@ -1,15 +1,291 @@
-use crate::Plugin;
-use signals::PlayerSignal;
-use state::State;
+use crate::{AssetRegistry, MissingAssets, Plugin};
+use libloader::libloading::Error as LibError;
+use signals::{PlayerSignal, ServerSignal};
+use state::{
+    AttackError, DigError, Direction, EntityId, EquipSlot, InteractError, InventoryError,
+    MoveError, PLAYER_ENTITY_TYPE_ID, PlayerSpawn, PluginEvent, State,
+};
+use std::{fmt, io, mem::take, path::PathBuf};
+
+fn make_direction(direction: signals::Direction) -> Direction {
+    match direction {
+        signals::Direction::Up => Direction::Up,
+        signals::Direction::Down => Direction::Down,
+        signals::Direction::Left => Direction::Left,
+        signals::Direction::Right => Direction::Right,
+    }
+}
+
+fn make_move_error(error: MoveError) -> signals::MoveError {
+    match error {
+        MoveError::NoEntity => signals::MoveError::NoEntity,
+        MoveError::Dead => signals::MoveError::Dead,
+        MoveError::NoFloor => signals::MoveError::NoFloor,
+        MoveError::NotWalkable => signals::MoveError::NotWalkable,
+        MoveError::Wall => signals::MoveError::Wall,
+        MoveError::Decoration => signals::MoveError::Decoration,
+        MoveError::Occupied => signals::MoveError::Occupied,
+    }
+}
+
+fn make_dig_error(error: DigError) -> signals::DigError {
+    match error {
+        DigError::NoEntity => signals::DigError::NoEntity,
+        DigError::Dead => signals::DigError::Dead,
+        DigError::NoWall => signals::DigError::NoWall,
+        DigError::Unbreakable => signals::DigError::Unbreakable,
+        DigError::NoTool => signals::DigError::NoTool,
+        DigError::WrongTool => signals::DigError::WrongTool,
+    }
+}
+
+fn make_interact_error(error: InteractError) -> signals::InteractError {
+    match error {
+        InteractError::NoEntity => signals::InteractError::NoEntity,
+        InteractError::Dead => signals::InteractError::Dead,
+        InteractError::NoDecoration => signals::InteractError::NoDecoration,
+        InteractError::NotInteractable => signals::InteractError::NotInteractable,
+    }
+}
+
+fn make_attack_error(error: AttackError) -> signals::AttackError {
+    match error {
+        AttackError::NoEntity => signals::AttackError::NoEntity,
+        AttackError::Dead => signals::AttackError::Dead,
+        AttackError::NoWeapon => signals::AttackError::NoWeapon,
+        AttackError::NoProjectile => signals::AttackError::NoProjectile,
+    }
+}
+
+const fn make_equip_slot(slot: signals::EquipSlot) -> EquipSlot {
+    match slot {
+        signals::EquipSlot::Weapon => EquipSlot::Weapon,
+        signals::EquipSlot::DiggingTool => EquipSlot::DiggingTool,
+        signals::EquipSlot::Torch => EquipSlot::Torch,
+    }
+}
+
+fn make_inventory_rejection(result: Result<(), InventoryError>) -> Option<ServerSignal> {
+    let error = match result.err()? {
+        InventoryError::NoEntity => signals::InventoryError::NoEntity,
+        InventoryError::Dead => signals::InventoryError::Dead,
+        InventoryError::InvalidSlot => signals::InventoryError::InvalidSlot,
+        InventoryError::EmptySlot => signals::InventoryError::EmptySlot,
+        InventoryError::NotEquippable => signals::InventoryError::NotEquippable,
+        InventoryError::Full => signals::InventoryError::Full,
+        InventoryError::NothingToPickUp => signals::InventoryError::NothingToPickUp,
+    };
+
+    Some(ServerSignal::InventoryRejected(error))
+}
+
+#[derive(Debug)]
+pub enum ReloadError {
+    Plugin(LibError),
+    Assets(io::Error),
+    MissingAssets(MissingAssets),
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plugin(error) => write!(f, "failed to load plugin: {error}"),
+            Self::Assets(error) => write!(f, "failed to load assets: {error}"),
+            Self::MissingAssets(error) => error.fmt(f),
+        }
+    }
+}
 
-#[derive(Default)]
 pub struct ServerUpdater {
     pub(crate) state: State,
+    pub(crate) assets: AssetRegistry,
     pub plugins: Vec<Plugin>,
+    // kept apart from `plugins` so that a failed reload can be retried
+    plugin_paths: Vec<PathBuf>,
+    reloaded: bool,
 }
 
 impl ServerUpdater {
-    pub(crate) fn handle_signal(&mut self, signal: PlayerSignal) {
-        println!("received {:?}!", signal);
+    pub fn new(assets: AssetRegistry) -> Self {
+        Self {
+            state: State::default(),
+            assets,
+            plugins: Vec::default(),
+            plugin_paths: Vec::default(),
+            reloaded: false,
+        }
+    }
+}
+
+impl ServerUpdater {
+    pub(crate) fn handle_signal(
+        &mut self,
+        entity_id: EntityId,
+        signal: PlayerSignal,
+    ) -> Option<ServerSignal> {
+        match signal {
+            PlayerSignal::ReloadServer => self.handle_reload(),
+
+            PlayerSignal::Move(direction) => (self.state)
+                .move_entity(entity_id, make_direction(direction))
+                .err()
+                .map(|error| ServerSignal::MoveRejected(make_move_error(error))),
+
+            PlayerSignal::Dig(direction) => (self.state)
+                .dig(entity_id, make_direction(direction))
+                .err()
+                .map(|error| ServerSignal::DigRejected(make_dig_error(error))),
+
+            PlayerSignal::Interact(direction) => (self.state)
+                .interact(entity_id, make_direction(direction))
+                .err()
+                .map(|error| ServerSignal::InteractRejected(make_interact_error(error))),
+
+            PlayerSignal::Attack(direction) => (self.state)
+                .attack(entity_id, make_direction(direction))
+                .err()
+                .map(|error| ServerSignal::AttackRejected(make_attack_error(error))),
+
+            PlayerSignal::Equip(index) => {
+                make_inventory_rejection(self.state.equip(entity_id, index))
+            }
+
+            PlayerSignal::Unequip(slot) => {
+                make_inventory_rejection(self.state.unequip(entity_id, make_equip_slot(slot)))
+            }
+
+            PlayerSignal::Swap(index, other_index) => {
+                make_inventory_rejection(self.state.swap_items(entity_id, index, other_index))
+            }
+
+            PlayerSignal::Drop(index) => {
+                make_inventory_rejection(self.state.drop_item(entity_id, index))
+            }
+
+            PlayerSignal::PickUp => make_inventory_rejection(self.state.pick_up(entity_id)),
+
+            PlayerSignal::Custom(message) => {
+                self.state
+                    .push_event(PluginEvent::Custom { entity_id, message });
+                None
+            }
+        }
+    }
+
+    pub(crate) fn handle_spectator_signal(&mut self, signal: PlayerSignal) -> Option<ServerSignal> {
+        match signal {
+            PlayerSignal::ReloadServer => self.handle_reload(),
+
+            // spectators have no entity to act with
+            _ => None,
+        }
+    }
+
+    fn handle_reload(&mut self) -> Option<ServerSignal> {
+        match self.reload() {
+            Ok(()) => {
+                println!("reloaded!");
+                None
+            }
+
+            Err(error) => {
+                println!("error: {}!", error);
+                Some(ServerSignal::ReloadFailed(error.to_string()))
+            }
+        }
+    }
+
+    /// Asks plugins in order until one of them provides a spawn
+    pub(crate) fn spawn_player(&mut self, username: &str) -> Option<EntityId> {
+        let PlayerSpawn { pos, asset, items } = (self.plugins.iter_mut())
+            .find_map(|plugin| plugin.spawn_player(&self.state, username))?;
+
+        let entity = PLAYER_ENTITY_TYPE_ID.instance(pos).asset(asset);
+        if let Err(error) = self.assets.check_entity(&self.state, &entity) {
+            println!("error: {}!", error);
+            return None;
+        }
+
+        let entity_id = match self.state.spawn(entity) {
+            Ok(entity_id) => entity_id,
+            Err(error) => {
+                println!("error: {:?}!", error);
+                return None;
+            }
+        };
+        for item in items {
+            if let Err(error) = self.state.give_item(entity_id, item) {
+                println!("error: {:?}!", error);
+            }
+        }
+        Some(entity_id)
+    }
+
+    pub(crate) fn beat(&mut self, number: u64) {
+        self.state.beat_ai(number);
+        self.state.push_event(PluginEvent::Beat { number });
+    }
+
+    pub(crate) fn tick(&mut self) {
+        self.state.tick_projectiles();
+        self.state.tick_ai();
+        self.state.push_event(PluginEvent::Tick);
+        self.dispatch_events();
+    }
+
+    // events pushed by plugins while handling are delivered on the next call
+    pub(crate) fn dispatch_events(&mut self) {
+        for event in self.state.take_events() {
+            for plugin in &mut self.plugins {
+                plugin.handle_event(&mut self.state, &event);
+            }
+        }
+    }
+
+    pub(crate) fn load_plugin(&mut self, path: impl Into<PathBuf>) -> Result<(), LibError> {
+        let path = path.into();
+        let plugin = Plugin::load(&path, &mut self.state)?;
+        self.plugins.push(plugin);
+        self.plugin_paths.push(path);
+        Ok(())
+    }
+
+    /// Unloads every plugin and loads it again from the same path against a fresh `State`
+    ///
+    /// Once the assets have loaded, the previous `State` is gone even if the rest fails, then the server is left with an empty `State`
+    /// and no plugins until the next reload. Clients are resynced to whatever is live either way
+    pub(crate) fn reload(&mut self) -> Result<(), ReloadError> {
+        let assets = AssetRegistry::load(self.assets.root()).map_err(ReloadError::Assets)?;
+
+        // behaviours in the `State` point into the library, which has to be unloaded before it's loaded from the same path again
+        self.state = State::default();
+        self.plugins.clear();
+        self.assets = assets;
+        self.reloaded = true;
+
+        let result = self.load_plugins();
+        if result.is_err() {
+            // in the same order as above
+            self.state = State::default();
+            self.plugins.clear();
+        }
+        result
+    }
+
+    fn load_plugins(&mut self) -> Result<(), ReloadError> {
+        for path in &self.plugin_paths {
+            let plugin = Plugin::load(path, &mut self.state).map_err(ReloadError::Plugin)?;
+            self.plugins.push(plugin);
+        }
+
+        (self.assets.check(&self.state)).map_err(ReloadError::MissingAssets)
+    }
+
+    pub(crate) const fn reloaded(&self) -> bool {
+        self.reloaded
+    }
+
+    pub(crate) fn take_reloaded(&mut self) -> bool {
+        take(&mut self.reloaded)
     }
 }
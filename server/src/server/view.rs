@@ -0,0 +1,169 @@
+use super::connector::{
+    make_decoration, make_entity, make_entity_id, make_floor, make_ground_item, make_wall,
+};
+use crate::AssetRegistry;
+use state::{Changes, EntityId, LightMap, State};
+use std::collections::HashSet;
+
+// in tiles around the player's entity, extended by the radius of the light it holds
+const VIEW_RADIUS: i32 = 5;
+
+/// What a player's client has been sent, tiles out of view are left on the client as they were last seen
+#[derive(Default)]
+pub struct PlayerView {
+    visible: HashSet<(i32, i32)>,
+    entities: HashSet<EntityId>,
+    // light levels the client has, stale tiles included
+    light: LightMap,
+}
+
+impl PlayerView {
+    /// Without `changes` everything in view is sent along with the assets, for a client that has nothing yet
+    pub fn update(
+        &mut self,
+        assets: &AssetRegistry,
+        state: &State,
+        light: &LightMap,
+        entity_id: EntityId,
+        changes: Option<&Changes>,
+    ) -> signals::ClientUpdate {
+        let radius = VIEW_RADIUS + state.held_light(entity_id).map_or(0, |light| light.radius);
+        let visible = (state.entities().get(&entity_id))
+            .map(|entity| state.field_of_view(entity.pos, radius))
+            .unwrap_or_default();
+
+        // everything that came into view and whatever has changed in view
+        let mut tiles = (visible.difference(&self.visible))
+            .copied()
+            .collect::<HashSet<_>>();
+        if let Some(changes) = changes {
+            let changed = (changes.floors.iter())
+                .chain(&changes.walls)
+                .chain(&changes.decorations)
+                .chain(&changes.items);
+            tiles.extend(changed.filter(|&key| visible.contains(key)));
+        }
+
+        let floors = (tiles.iter())
+            .map(|&(x, y)| {
+                let floor = state.floors().get(&(x, y));
+                (
+                    signals::Position::new(x, y),
+                    floor.map(|floor| make_floor(assets, state, floor)),
+                )
+            })
+            .collect();
+
+        let walls = (tiles.iter())
+            .map(|&(x, y)| {
+                let wall = state.walls().get(&(x, y));
+                (
+                    signals::Position::new(x, y),
+                    wall.map(|wall| make_wall(assets, state, wall)),
+                )
+            })
+            .collect();
+
+        let decorations = (tiles.iter())
+            .map(|&(x, y)| {
+                let decoration = state.decorations().get(&(x, y));
+                (
+                    signals::Position::new(x, y),
+                    decoration.map(|decoration| make_decoration(assets, state, decoration)),
+                )
+            })
+            .collect();
+
+        let items = (tiles.iter())
+            .map(|&(x, y)| {
+                let items = state.items().get(&(x, y));
+                (
+                    signals::Position::new(x, y),
+                    items.and_then(|items| make_ground_item(assets, state, items)),
+                )
+            })
+            .collect();
+
+        // entities out of view are removed rather than left stale, since they move around
+        let visible_entities = (visible.iter())
+            .flat_map(|&pos| state.entities_at(pos).iter().copied())
+            .collect::<HashSet<_>>();
+
+        let shown = (visible_entities.iter())
+            .filter(|&entity_id| {
+                !self.entities.contains(entity_id)
+                    || changes.is_none_or(|changes| changes.entities.contains(entity_id))
+            })
+            .map(|&entity_id| {
+                let entity = &state.entities()[&entity_id];
+                (
+                    make_entity_id(entity_id),
+                    Some(make_entity(assets, state, entity)),
+                )
+            });
+        let hidden = (self.entities.difference(&visible_entities))
+            .map(|&entity_id| (make_entity_id(entity_id), None));
+        let entities = shown.chain(hidden).collect();
+
+        let stale = (self.visible.difference(&visible))
+            .map(|&(x, y)| (signals::Position::new(x, y), true))
+            .chain(
+                (visible.difference(&self.visible))
+                    .map(|&(x, y)| (signals::Position::new(x, y), false)),
+            )
+            .collect();
+
+        let light = self.update_light(light, visible.iter().copied());
+
+        self.visible = visible;
+        self.entities = visible_entities;
+
+        signals::ClientUpdate {
+            assets: if changes.is_none() {
+                assets.all()
+            } else {
+                Box::default()
+            },
+            floors,
+            walls,
+            decorations,
+            entities,
+            items,
+            stale,
+            light,
+        }
+    }
+
+    /// Spectators see the whole light map
+    pub fn update_spectator_light(&mut self, light: &LightMap) -> Box<[(signals::Position, u8)]> {
+        let tiles = (self.light.keys())
+            .chain(light.keys())
+            .copied()
+            .collect::<HashSet<_>>();
+        self.update_light(light, tiles)
+    }
+
+    // levels of `tiles` that differ from what the client has
+    fn update_light(
+        &mut self,
+        light: &LightMap,
+        tiles: impl IntoIterator<Item = (i32, i32)>,
+    ) -> Box<[(signals::Position, u8)]> {
+        let mut res = Vec::new();
+        for (x, y) in tiles {
+            let level = light.get(&(x, y)).copied().unwrap_or_default();
+            if self.light.get(&(x, y)).copied().unwrap_or_default() == level {
+                continue;
+            }
+
+            if level == 0 {
+                self.light.remove(&(x, y));
+            } else {
+                self.light.insert((x, y), level);
+            }
+            res.push((signals::Position::new(x, y), level));
+        }
+
+        res.into()
+    }
+}
@@ -0,0 +1,73 @@
+use super::{ClientUpdate, EntityId};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Has to be bumped on every change to the signals sent between the client and the server
+pub const PROTOCOL_VERSION: u32 = 14;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Auth {
+    // has to stay the first field, see `Auth::decode`
+    pub protocol_version: u32,
+    pub username: String,
+}
+
+impl Auth {
+    pub const fn new(username: String) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            username,
+        }
+    }
+
+    /// Checks the protocol version before decoding the rest, which may have a different layout
+    pub fn decode(frame: &[u8]) -> Result<Self, RejectReason> {
+        let protocol_version = bincode::deserialize::<u32>(frame)
+            .map_err(|error| RejectReason::Malformed(error.to_string()))?;
+
+        if protocol_version != PROTOCOL_VERSION {
+            return Err(RejectReason::ProtocolMismatch {
+                server: PROTOCOL_VERSION,
+                client: protocol_version,
+            });
+        }
+
+        bincode::deserialize(frame).map_err(|error| RejectReason::Malformed(error.to_string()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Authorized {
+    // `None` for spectators
+    pub player_entity_id: Option<EntityId>,
+    pub update: ClientUpdate,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum RejectReason {
+    ProtocolMismatch { server: u32, client: u32 },
+    Malformed(String),
+    Timeout,
+    AlreadyConnected,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ProtocolMismatch { server, client } => write!(
+                f,
+                "protocol version mismatch: server has {server}, client has {client}"
+            ),
+            Self::Malformed(error) => write!(f, "malformed auth: {error}"),
+            Self::Timeout => write!(f, "auth timed out"),
+            Self::AlreadyConnected => write!(f, "already connected"),
+        }
+    }
+}
+
+// variants mustn't be reordered, so that clients of other versions can still read rejections
+#[derive(Debug, Serialize, Deserialize)]
+pub enum AuthReply {
+    Rejected { reason: RejectReason },
+    Authorized(Authorized),
+}
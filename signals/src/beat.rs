@@ -0,0 +1,25 @@
+use super::PlayerSignal;
+use serde::{Deserialize, Serialize};
+
+/// Timestamps are milliseconds since the server started, beats happen on multiples of `interval`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Beat {
+    pub number: u64,
+    // when the signal was sent rather than when the beat happened, beats are only sent between ticks
+    pub timestamp: u64,
+    pub interval: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Groove {
+    // actions on consecutive beats
+    pub streak: u32,
+    pub multiplier: u32,
+}
+
+/// Every `PlayerSignal` is sent stamped with the client's estimate of the server time
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StampedSignal {
+    pub timestamp: u64,
+    pub signal: PlayerSignal,
+}
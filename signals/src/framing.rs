@@ -0,0 +1,275 @@
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+const HEADER_SIZE: usize = size_of::<u32>();
+const READ_CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub enum FrameError {
+    Io(io::Error),
+    Closed,
+    TooLarge(usize),
+    Bincode(bincode::Error),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => error.fmt(f),
+            Self::Closed => write!(f, "connection closed"),
+            Self::TooLarge(size) => write!(f, "frame of {size} bytes exceeds {MAX_FRAME_SIZE}"),
+            Self::Bincode(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for FrameError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<bincode::Error> for FrameError {
+    fn from(error: bincode::Error) -> Self {
+        Self::Bincode(error)
+    }
+}
+
+fn encode_frame<T: Serialize>(message: &T) -> Result<Vec<u8>, FrameError> {
+    let body = bincode::serialize(message)?;
+    if body.len() > MAX_FRAME_SIZE {
+        return Err(FrameError::TooLarge(body.len()));
+    }
+
+    let mut frame = bincode::serialize(&(body.len() as u32))?;
+    frame.extend(body);
+    Ok(frame)
+}
+
+/// Writes `message` prefixed by its length, a non-blocking `writer` should go through [`FrameWriter`] instead
+pub fn write_frame<T: Serialize>(writer: &mut impl Write, message: &T) -> Result<(), FrameError> {
+    writer.write_all(&encode_frame(message)?)?;
+    Ok(())
+}
+
+/// Per-connection send buffer, so that frames a non-blocking writer hasn't taken yet aren't cut off
+#[derive(Debug, Default)]
+pub struct FrameWriter {
+    buffer: Vec<u8>,
+}
+
+impl FrameWriter {
+    pub fn push<T: Serialize>(&mut self, message: &T) -> Result<(), FrameError> {
+        self.buffer.extend(encode_frame(message)?);
+        Ok(())
+    }
+
+    /// Bytes that haven't been written yet
+    pub const fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Writes as much of the buffer as `writer` takes without blocking, the rest is kept for the next call
+    pub fn flush(&mut self, writer: &mut impl Write) -> Result<(), FrameError> {
+        let mut written = 0;
+        let result = loop {
+            let Some(rest) = self.buffer.get(written..).filter(|rest| !rest.is_empty()) else {
+                break Ok(());
+            };
+
+            match writer.write(rest) {
+                Ok(0) => break Err(FrameError::Closed),
+                Ok(len) => written += len,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => break Err(error.into()),
+            }
+        };
+
+        self.buffer.drain(..written);
+        result
+    }
+}
+
+/// Per-connection receive buffer which only yields whole frames
+#[derive(Debug, Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    /// Returns `Ok(None)` once a non-blocking `reader` has no whole frame available yet
+    pub fn read<T: DeserializeOwned>(
+        &mut self,
+        reader: &mut impl Read,
+    ) -> Result<Option<T>, FrameError> {
+        let Some(frame) = self.read_frame(reader)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(bincode::deserialize(&frame)?))
+    }
+
+    /// Same as [`FrameReader::read`], but leaves the frame body undecoded
+    pub fn read_frame(&mut self, reader: &mut impl Read) -> Result<Option<Vec<u8>>, FrameError> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+
+        loop {
+            if let Some(frame) = self.next_frame()? {
+                return Ok(Some(frame));
+            }
+
+            match reader.read(&mut chunk) {
+                Ok(0) => return Err(FrameError::Closed),
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        let Some(header) = self.buffer.get(..HEADER_SIZE) else {
+            return Ok(None);
+        };
+
+        let len = bincode::deserialize::<u32>(header)? as usize;
+        if len > MAX_FRAME_SIZE {
+            return Err(FrameError::TooLarge(len));
+        }
+
+        if self.buffer.len() < HEADER_SIZE + len {
+            return Ok(None);
+        }
+
+        let frame = self
+            .buffer
+            .drain(..HEADER_SIZE + len)
+            .skip(HEADER_SIZE)
+            .collect();
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // yields the chunks one `read` at a time, then blocks
+    struct Chunks(Vec<Vec<u8>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            let chunk = self.0.remove(0);
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    // takes at most `limit` bytes in total, then blocks
+    struct Limited {
+        written: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.limit - self.written.len());
+            if len == 0 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            self.written.extend(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn frame(message: &str) -> Vec<u8> {
+        encode_frame(&message.to_owned()).unwrap()
+    }
+
+    fn read_all(reader: &mut FrameReader, chunks: &mut Chunks) -> Vec<String> {
+        let mut res = Vec::new();
+        while let Some(message) = reader.read(chunks).unwrap() {
+            res.push(message);
+        }
+        res
+    }
+
+    #[test]
+    fn split_headers_are_waited_for() {
+        let hello = frame("hello");
+        let mut chunks = Chunks(vec![hello[..2].to_vec()]);
+        let mut reader = FrameReader::default();
+        assert!(read_all(&mut reader, &mut chunks).is_empty());
+
+        chunks.0.push(hello[2..].to_vec());
+        assert_eq!(read_all(&mut reader, &mut chunks), ["hello"]);
+    }
+
+    #[test]
+    fn split_bodies_are_waited_for() {
+        let hello = frame("hello");
+        let mut chunks = Chunks(vec![hello[..HEADER_SIZE + 3].to_vec()]);
+        let mut reader = FrameReader::default();
+        assert!(read_all(&mut reader, &mut chunks).is_empty());
+
+        chunks.0.push(hello[HEADER_SIZE + 3..].to_vec());
+        assert_eq!(read_all(&mut reader, &mut chunks), ["hello"]);
+    }
+
+    #[test]
+    fn frames_in_one_read_are_all_yielded() {
+        let mut bytes = frame("a");
+        bytes.extend(frame("bc"));
+        bytes.extend(&frame("def")[..3]);
+
+        let mut chunks = Chunks(vec![bytes]);
+        let mut reader = FrameReader::default();
+        assert_eq!(read_all(&mut reader, &mut chunks), ["a", "bc"]);
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected_by_their_header() {
+        let header = bincode::serialize(&(MAX_FRAME_SIZE as u32 + 1)).unwrap();
+        let mut chunks = Chunks(vec![header]);
+        let result = FrameReader::default().read_frame(&mut chunks);
+        assert!(matches!(result, Err(FrameError::TooLarge(len)) if len == MAX_FRAME_SIZE + 1));
+    }
+
+    #[test]
+    fn unwritten_bytes_are_kept_for_the_next_flush() {
+        let mut writer = FrameWriter::default();
+        writer.push(&"hello".to_owned()).unwrap();
+        writer.push(&"world".to_owned()).unwrap();
+
+        let mut stream = Limited {
+            written: Vec::new(),
+            limit: 7,
+        };
+        writer.flush(&mut stream).unwrap();
+        assert_eq!(writer.pending(), 2 * frame("hello").len() - 7);
+
+        stream.limit = usize::MAX;
+        writer.flush(&mut stream).unwrap();
+        assert_eq!(writer.pending(), 0);
+
+        let mut chunks = Chunks(vec![stream.written]);
+        let mut reader = FrameReader::default();
+        assert_eq!(read_all(&mut reader, &mut chunks), ["hello", "world"]);
+    }
+}
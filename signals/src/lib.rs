@@ -1,21 +1,103 @@
 use serde::{Deserialize, Serialize};
 
+mod auth;
+mod beat;
+mod framing;
 mod state;
 
+pub use auth::*;
+pub use beat::*;
+pub use framing::*;
 pub use state::*;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Auth {
-    pub username: String,
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct Authorized {
-    pub player_entity_id: EntityId,
-    pub update: ClientUpdate,
+pub enum PlayerSignal {
+    ReloadServer,
+    Move(Direction),
+    Dig(Direction),
+    Interact(Direction),
+    Attack(Direction),
+    /// Bag index
+    Equip(usize),
+    Unequip(EquipSlot),
+    /// Bag indices
+    Swap(usize, usize),
+    /// Bag index
+    Drop(usize),
+    PickUp,
+    Custom(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    NoEntity,
+    Dead,
+    NoFloor,
+    NotWalkable,
+    Wall,
+    Decoration,
+    Occupied,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DigError {
+    NoEntity,
+    Dead,
+    NoWall,
+    Unbreakable,
+    NoTool,
+    WrongTool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum InteractError {
+    NoEntity,
+    Dead,
+    NoDecoration,
+    NotInteractable,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AttackError {
+    NoEntity,
+    Dead,
+    NoWeapon,
+    NoProjectile,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryError {
+    NoEntity,
+    Dead,
+    InvalidSlot,
+    EmptySlot,
+    NotEquippable,
+    Full,
+    NothingToPickUp,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub enum PlayerSignal {
-    ReloadServer,
+pub enum ServerSignal {
+    Update(ClientUpdate),
+    Resync(Authorized),
+    MoveRejected(MoveError),
+    DigRejected(DigError),
+    InteractRejected(InteractError),
+    AttackRejected(AttackError),
+    Inventory(Inventory),
+    InventoryRejected(InventoryError),
+    Beat(Beat),
+    /// The action was outside of the tolerance window around a beat
+    OffBeat,
+    Groove(Groove),
+    /// Why `PlayerSignal::ReloadServer` has failed, everyone is resynced to the emptied world either way
+    ReloadFailed(String),
 }
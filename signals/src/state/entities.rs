@@ -1,11 +1,23 @@
 use super::{AssetId, Position};
 use serde::{Deserialize, Serialize};
 
+/// Indices are reused by the server, the generation tells entities on the same index apart
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct EntityId(pub usize);
+pub struct EntityId {
+    pub index: u32,
+    pub generation: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Entity {
     pub asset_id: AssetId,
     pub pos: Position,
+    // `None` for invulnerable entities, zero for corpses
+    pub health: Option<Health>,
 }
@@ -0,0 +1,21 @@
+use super::AssetId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipSlot {
+    Weapon,
+    DiggingTool,
+    Torch,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Item {
+    pub asset_id: AssetId,
+}
+
+/// Only sent to the owner of the inventory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Inventory {
+    pub bag: Box<[Option<Item>]>,
+    pub equipped: Box<[(EquipSlot, Item)]>,
+}
@@ -2,9 +2,11 @@ use serde::{Deserialize, Serialize};
 
 mod assets;
 mod entities;
+mod items;
 
 pub use assets::*;
 pub use entities::*;
+pub use items::*;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Position {
@@ -23,10 +25,40 @@ pub struct Tile {
     pub asset_id: AssetId,
 }
 
+pub const DAMAGE_STAGES: u8 = 4;
+
+/// `damage_stage` goes from 0 for an intact wall up to `DAMAGE_STAGES - 1`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WallTile {
+    pub asset_id: AssetId,
+    pub damage_stage: u8,
+}
+
+/// `None` removes the object on the client
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClientUpdate {
     pub assets: Box<[(AssetId, AssetData)]>,
-    pub floors: Box<[(Position, Tile)]>,
-    pub walls: Box<[(Position, Tile)]>,
-    pub entities: Box<[(EntityId, Entity)]>,
+    pub floors: Box<[(Position, Option<Tile>)]>,
+    pub walls: Box<[(Position, Option<WallTile>)]>,
+    pub decorations: Box<[(Position, Option<Tile>)]>,
+    pub entities: Box<[(EntityId, Option<Entity>)]>,
+    // only the topmost item of each tile
+    pub items: Box<[(Position, Option<Tile>)]>,
+    /// Tiles that went out of view (`true`) or came back into it (`false`), stale ones are shown as last seen
+    pub stale: Box<[(Position, bool)]>,
+    /// Light levels of tiles that have changed, `0` for darkness
+    pub light: Box<[(Position, u8)]>,
+}
+
+impl ClientUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+            && self.floors.is_empty()
+            && self.walls.is_empty()
+            && self.decorations.is_empty()
+            && self.entities.is_empty()
+            && self.items.is_empty()
+            && self.stale.is_empty()
+            && self.light.is_empty()
+    }
 }
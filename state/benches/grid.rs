@@ -0,0 +1,244 @@
+//! Compares `ChunkedGrid` against a `HashMap<(i32, i32), _>` on a large generated dungeon.
+//! The map it has replaced used the default SipHash, the one here uses the same FxHash as the chunks so that only the storage differs
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use state::*;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{BuildHasherDefault, Hasher},
+    hint::black_box,
+};
+
+// a copy of the FxHash `ChunkedGrid` hashes its chunk keys with
+#[derive(Default)]
+struct FxHasher(u64);
+
+impl Hasher for FxHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(u64::from(byte));
+        }
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u64(u64::from(n as u32));
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
+
+type TileMap<T> = HashMap<(i32, i32), T, BuildHasherDefault<FxHasher>>;
+
+const DUNGEON_SIZE: i32 = 512;
+const ROOMS: usize = 400;
+// the same as the server's view radius with a torch
+const VIEW_RADIUS: i32 = 10;
+
+struct Dungeon {
+    state: State,
+    floors: TileMap<Floor>,
+    walls: TileMap<Wall>,
+    // where the views are looked from
+    centers: Vec<Vec2>,
+}
+
+// solid dirt with rooms carved out of it, joined one after another by corridors
+fn dungeon() -> Dungeon {
+    let mut state = State::default();
+    let stone = state.insert_type(FloorType::new("stone"));
+    let dirt = state.insert_type(WallType::new("dirt"));
+    let mut rng = Rng::new(0);
+
+    let mut carved = HashSet::new();
+    let mut centers = Vec::new();
+    for _ in 0..ROOMS {
+        let size = vec2(4 + rng.below(12) as i32, 4 + rng.below(12) as i32);
+        let min = vec2(
+            1 + rng.below((DUNGEON_SIZE - size.x - 2) as u64) as i32,
+            1 + rng.below((DUNGEON_SIZE - size.y - 2) as u64) as i32,
+        );
+        let max = min + size;
+
+        carved.extend((min.x..=max.x).flat_map(|x| (min.y..=max.y).map(move |y| (x, y))));
+        centers.push((min + max) / 2);
+    }
+
+    for room in centers.windows(2) {
+        let (from, to) = (room[0], room[1]);
+        carved.extend((from.x.min(to.x)..=from.x.max(to.x)).map(|x| (x, from.y)));
+        carved.extend((from.y.min(to.y)..=from.y.max(to.y)).map(|y| (to.x, y)));
+    }
+
+    state.place_rect(
+        (0, 0),
+        (DUNGEON_SIZE - 1, DUNGEON_SIZE - 1),
+        stone.instance(),
+    );
+    for x in 0..DUNGEON_SIZE {
+        for y in 0..DUNGEON_SIZE {
+            if !carved.contains(&(x, y)) {
+                state.place((x, y), dirt.instance());
+            }
+        }
+    }
+
+    let floors = (state.floors().iter())
+        .map(|(key, floor)| (key, floor.clone()))
+        .collect();
+    let walls = (state.walls().iter())
+        .map(|(key, wall)| (key, wall.clone()))
+        .collect();
+    state.take_changes();
+
+    Dungeon {
+        state,
+        floors,
+        walls,
+        centers,
+    }
+}
+
+fn generate(c: &mut Criterion) {
+    let Dungeon { floors, walls, .. } = dungeon();
+    let mut group = c.benchmark_group("generate");
+
+    group.bench_function("hashmap", |b| {
+        b.iter(|| {
+            let mut res_floors = TileMap::default();
+            for (&key, floor) in &floors {
+                res_floors.insert(key, floor.clone());
+            }
+            let mut res_walls = TileMap::default();
+            for (&key, wall) in &walls {
+                res_walls.insert(key, wall.clone());
+            }
+            black_box((res_floors, res_walls))
+        });
+    });
+
+    group.bench_function("chunked", |b| {
+        b.iter(|| {
+            let mut res_floors = ChunkedGrid::default();
+            for (&key, floor) in &floors {
+                res_floors.insert(key, floor.clone());
+            }
+            let mut res_walls = ChunkedGrid::default();
+            for (&key, wall) in &walls {
+                res_walls.insert(key, wall.clone());
+            }
+            black_box((res_floors, res_walls))
+        });
+    });
+}
+
+fn region(c: &mut Criterion) {
+    let dungeon = dungeon();
+    let mut group = c.benchmark_group("region");
+
+    group.bench_function("hashmap", |b| {
+        b.iter(|| {
+            let mut res = 0;
+            for &center in &dungeon.centers {
+                for y in center.y - VIEW_RADIUS..=center.y + VIEW_RADIUS {
+                    for x in center.x - VIEW_RADIUS..=center.x + VIEW_RADIUS {
+                        res += usize::from(dungeon.walls.contains_key(&(x, y)));
+                    }
+                }
+            }
+            black_box(res)
+        });
+    });
+
+    group.bench_function("chunked", |b| {
+        b.iter(|| {
+            let mut res = 0;
+            for &center in &dungeon.centers {
+                let radius = vec2(VIEW_RADIUS, VIEW_RADIUS);
+                res += dungeon
+                    .state
+                    .walls()
+                    .region(center - radius, center + radius)
+                    .count();
+            }
+            black_box(res)
+        });
+    });
+}
+
+// what the server goes through to build the first `ClientUpdate` of a spectator, who sees everything
+fn init_update(c: &mut Criterion) {
+    let dungeon = dungeon();
+    let mut group = c.benchmark_group("init_update");
+
+    group.bench_function("hashmap", |b| {
+        b.iter(|| {
+            let floors = (dungeon.floors.iter())
+                .map(|(&key, floor)| (key, floor.type_id))
+                .collect::<Vec<_>>();
+            let walls = (dungeon.walls.iter())
+                .map(|(&key, wall)| (key, wall.type_id))
+                .collect::<Vec<_>>();
+            black_box((floors, walls))
+        });
+    });
+
+    group.bench_function("chunked", |b| {
+        b.iter(|| {
+            let floors = (dungeon.state.floors().iter())
+                .map(|(key, floor)| (key, floor.type_id))
+                .collect::<Vec<_>>();
+            let walls = (dungeon.state.walls().iter())
+                .map(|(key, wall)| (key, wall.type_id))
+                .collect::<Vec<_>>();
+            black_box((floors, walls))
+        });
+    });
+}
+
+// what the server goes through to build a `ClientUpdate` for a player who has just come into a room
+fn view_update(c: &mut Criterion) {
+    let dungeon = dungeon();
+    let views = (dungeon.centers.iter())
+        .map(|&center| dungeon.state.field_of_view(center, VIEW_RADIUS))
+        .collect::<Vec<_>>();
+    let mut group = c.benchmark_group("view_update");
+
+    group.bench_function("hashmap", |b| {
+        b.iter(|| {
+            for view in &views {
+                let tiles = (view.iter())
+                    .map(|key| {
+                        let floor = dungeon.floors.get(key).map(|floor| floor.type_id);
+                        let wall = dungeon.walls.get(key).map(|wall| wall.type_id);
+                        (*key, floor, wall)
+                    })
+                    .collect::<Vec<_>>();
+                black_box(tiles);
+            }
+        });
+    });
+
+    group.bench_function("chunked", |b| {
+        b.iter(|| {
+            for view in &views {
+                let tiles = (view.iter())
+                    .map(|key| {
+                        let floor = dungeon.state.floors().get(key).map(|floor| floor.type_id);
+                        let wall = dungeon.state.walls().get(key).map(|wall| wall.type_id);
+                        (*key, floor, wall)
+                    })
+                    .collect::<Vec<_>>();
+                black_box(tiles);
+            }
+        });
+    });
+}
+
+criterion_group!(benches, generate, region, init_update, view_update);
+criterion_main!(benches);
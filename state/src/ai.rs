@@ -0,0 +1,262 @@
+use crate::{EntityId, PLAYER_ENTITY_TYPE_ID, State, Vec2, vec2};
+use std::{collections::HashMap, fmt};
+
+// how far a patrolling entity looks for a way back to its route
+const PATROL_MAX_PATH: usize = 32;
+
+/// Attached to an entity type with `EntityType::behaviour`, runs for every living entity of that type
+pub trait Behaviour: fmt::Debug {
+    /// Returns whether the entity has acted
+    fn on_beat(&mut self, state: &mut State, entity_id: EntityId, beat: u64) -> bool;
+
+    fn on_tick(&mut self, _state: &mut State, _entity_id: EntityId) {}
+
+    /// Falls back to `other` on beats `self` doesn't act on
+    fn or<B: Behaviour>(self, other: B) -> Or<Self, B>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+}
+
+#[derive(Debug)]
+pub struct Or<A, B>(A, B);
+
+impl<A: Behaviour, B: Behaviour> Behaviour for Or<A, B> {
+    fn on_beat(&mut self, state: &mut State, entity_id: EntityId, beat: u64) -> bool {
+        self.0.on_beat(state, entity_id, beat) || self.1.on_beat(state, entity_id, beat)
+    }
+
+    fn on_tick(&mut self, state: &mut State, entity_id: EntityId) {
+        self.0.on_tick(state, entity_id);
+        self.1.on_tick(state, entity_id);
+    }
+}
+
+/// Lets `behaviour` act only on every `n`-th beat
+#[derive(Debug)]
+pub struct EveryNthBeat<B> {
+    n: u64,
+    behaviour: B,
+}
+
+impl<B> EveryNthBeat<B> {
+    pub fn new(n: u64, behaviour: B) -> Self {
+        Self {
+            n: n.max(1),
+            behaviour,
+        }
+    }
+}
+
+impl<B: Behaviour> Behaviour for EveryNthBeat<B> {
+    fn on_beat(&mut self, state: &mut State, entity_id: EntityId, beat: u64) -> bool {
+        beat.is_multiple_of(self.n) && self.behaviour.on_beat(state, entity_id, beat)
+    }
+
+    fn on_tick(&mut self, state: &mut State, entity_id: EntityId) {
+        self.behaviour.on_tick(state, entity_id);
+    }
+}
+
+/// Walks towards the nearest living player at most `sight` steps away and attacks them when next to them
+#[derive(Debug)]
+pub struct ChaseNearestPlayer {
+    sight: usize,
+}
+
+impl ChaseNearestPlayer {
+    pub const fn new(sight: usize) -> Self {
+        Self { sight }
+    }
+}
+
+impl Behaviour for ChaseNearestPlayer {
+    fn on_beat(&mut self, state: &mut State, entity_id: EntityId, _beat: u64) -> bool {
+        let Some(pos) = (state.entities.get(&entity_id)).map(|entity| entity.pos) else {
+            return false;
+        };
+
+        // a path can't be shorter than the distance along either axis
+        let sight = vec2(self.sight as i32, self.sight as i32);
+        let path = (state.entities_in_rect(pos - sight, pos + sight))
+            .filter(|&player_id| {
+                state.entities[&player_id].type_id == PLAYER_ENTITY_TYPE_ID
+                    && state.is_alive(player_id)
+            })
+            .filter_map(|player_id| {
+                state.find_path(pos, state.entities[&player_id].pos, self.sight)
+            })
+            .min_by_key(Vec::len);
+        let Some(path) = path else {
+            return false;
+        };
+        let Some(&direction) = path.first() else {
+            return false;
+        };
+
+        if path.len() == 1 {
+            state.attack(entity_id, direction).is_ok()
+        } else {
+            state.move_entity(entity_id, direction).is_ok()
+        }
+    }
+}
+
+/// Walks a loop through `points`, which are offsets from where each entity has first acted
+#[derive(Debug)]
+pub struct Patrol {
+    points: Vec<Vec2>,
+    // the origin and the index of the next point for every entity
+    progress: HashMap<EntityId, (Vec2, usize)>,
+}
+
+impl Patrol {
+    pub fn new(points: impl IntoIterator<Item = Vec2>) -> Self {
+        Self {
+            points: points.into_iter().collect(),
+            progress: HashMap::default(),
+        }
+    }
+}
+
+impl Behaviour for Patrol {
+    fn on_beat(&mut self, state: &mut State, entity_id: EntityId, _beat: u64) -> bool {
+        let Some(pos) = (state.entities.get(&entity_id)).map(|entity| entity.pos) else {
+            return false;
+        };
+        if self.points.is_empty() {
+            return false;
+        }
+
+        // despawned entities are never coming back under the same id
+        self.progress.retain(|&other, _| !state.is_stale(other));
+        let (origin, index) = self.progress.entry(entity_id).or_insert((pos, 0));
+        if pos == *origin + self.points[*index] {
+            *index = (*index + 1) % self.points.len();
+        }
+        let target = *origin + self.points[*index];
+
+        (state.find_path(pos, target, PATROL_MAX_PATH))
+            .and_then(|path| path.first().copied())
+            .is_some_and(|direction| state.move_entity(entity_id, direction).is_ok())
+    }
+}
+
+impl State {
+    /// Runs `Behaviour::on_beat` for every living non-player entity
+    pub fn beat_ai(&mut self, beat: u64) {
+        self.run_behaviours(|behaviour, state, entity_id| {
+            behaviour.on_beat(state, entity_id, beat);
+        });
+    }
+
+    pub fn tick_ai(&mut self) {
+        self.run_behaviours(|behaviour, state, entity_id| behaviour.on_tick(state, entity_id));
+    }
+
+    fn run_behaviours(&mut self, mut run: impl FnMut(&mut dyn Behaviour, &mut Self, EntityId)) {
+        let mut type_ids = (self.entity_types.iter())
+            .filter(|&(&type_id, entity_type)| {
+                type_id != PLAYER_ENTITY_TYPE_ID && entity_type.behaviour.is_some()
+            })
+            .map(|(&type_id, _)| type_id)
+            .collect::<Vec<_>>();
+        type_ids.sort();
+
+        for type_id in type_ids {
+            let mut entity_ids = (self.entities.iter())
+                .filter(|(_, entity)| entity.type_id == type_id)
+                .map(|(&entity_id, _)| entity_id)
+                .collect::<Vec<_>>();
+            entity_ids.sort();
+
+            // taken out while running, since it needs the whole `State`
+            let Some(mut behaviour) = (self.entity_types.get_mut(&type_id))
+                .and_then(|entity_type| entity_type.behaviour.take())
+            else {
+                continue;
+            };
+
+            for entity_id in entity_ids {
+                // the previous ones may have killed it
+                if self.is_alive(entity_id) {
+                    run(behaviour.as_mut(), self, entity_id);
+                }
+            }
+
+            if let Some(entity_type) = self.entity_types.get_mut(&type_id) {
+                entity_type.behaviour = Some(behaviour);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    // a skeleton with a dagger four tiles to the left of a player in an open field
+    fn field(behaviour: impl Behaviour + 'static) -> (State, EntityId, EntityId) {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let dagger = state.insert_type(ItemType::new("dagger").tool(ToolKind::Dagger).damage(1));
+        let skeleton = state.insert_type(EntityType::new("skeleton").behaviour(behaviour));
+
+        state.place_rect((0, 0), (9, 9), grass.instance());
+        let player = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(5, 5)))
+            .unwrap();
+        let skeleton = state.spawn(skeleton.instance(vec2(1, 5))).unwrap();
+        state.give_item(skeleton, dagger.instance()).unwrap();
+
+        (state, player, skeleton)
+    }
+
+    #[test]
+    fn chasers_walk_up_to_players_and_attack() {
+        let (mut state, player, skeleton) = field(ChaseNearestPlayer::new(8));
+
+        for beat in 0..3 {
+            state.beat_ai(beat);
+        }
+        assert_eq!(state.entities()[&skeleton].pos, vec2(4, 5));
+        assert_eq!(
+            state.entities()[&player].health.unwrap().current,
+            PLAYER_MAX_HEALTH
+        );
+
+        state.beat_ai(3);
+        assert_eq!(state.entities()[&skeleton].pos, vec2(4, 5));
+        assert_eq!(
+            state.entities()[&player].health.unwrap().current,
+            PLAYER_MAX_HEALTH - 1
+        );
+    }
+
+    #[test]
+    fn chasers_fall_back_when_nobody_is_in_sight() {
+        let (mut state, _, skeleton) =
+            field(ChaseNearestPlayer::new(2).or(Patrol::new([vec2(0, -2), vec2(0, 0)])));
+
+        for beat in 0..4 {
+            state.beat_ai(beat);
+        }
+        assert_eq!(state.entities()[&skeleton].pos, vec2(1, 5));
+
+        state.beat_ai(4);
+        assert_eq!(state.entities()[&skeleton].pos, vec2(1, 4));
+    }
+
+    #[test]
+    fn every_nth_beat_skips_the_others() {
+        let (mut state, _, skeleton) = field(EveryNthBeat::new(2, ChaseNearestPlayer::new(8)));
+
+        state.beat_ai(1);
+        assert_eq!(state.entities()[&skeleton].pos, vec2(1, 5));
+
+        state.beat_ai(2);
+        assert_eq!(state.entities()[&skeleton].pos, vec2(2, 5));
+    }
+}
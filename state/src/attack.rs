@@ -0,0 +1,248 @@
+use crate::{Direction, EntityId, EquipSlot, ObjectType, ProjectileType, State, ToolKind, Vec2};
+
+/// Tiles hit by a melee attack as `(side, forward)` offsets from the attacker, `side` going to the right of the facing
+pub type AttackShape = &'static [(i32, i32)];
+
+impl ToolKind {
+    /// Empty for ranged weapons, which shoot their `ItemType::projectile` instead
+    pub const fn attack_shape(self) -> AttackShape {
+        match self {
+            Self::Dagger | Self::Pickaxe | Self::Axe | Self::Shovel => &[(0, 1)],
+            Self::Sword => &[(-1, 1), (0, 1), (1, 1)],
+            Self::Scythe => &[(-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0)],
+            Self::Hammer => &[(0, 1), (0, 2)],
+            Self::Whip => &[(0, 1), (0, 2), (0, 3), (0, 4)],
+            Self::Bow | Self::Crossbow => &[],
+        }
+    }
+}
+
+impl Direction {
+    const fn rotate(self, (side, forward): (i32, i32)) -> Vec2 {
+        match self {
+            Self::Up => Vec2::new(side, -forward),
+            Self::Down => Vec2::new(-side, forward),
+            Self::Left => Vec2::new(-forward, -side),
+            Self::Right => Vec2::new(forward, side),
+        }
+    }
+}
+
+pub fn attack_tiles(
+    tool_kind: ToolKind,
+    origin: Vec2,
+    direction: Direction,
+) -> impl Iterator<Item = Vec2> {
+    (tool_kind.attack_shape().iter()).map(move |&offset| origin + direction.rotate(offset))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackError {
+    NoEntity,
+    Dead,
+    NoWeapon,
+    NoProjectile,
+}
+
+#[derive(Debug, Clone)]
+pub enum AttackKind {
+    Melee(Vec<Vec2>),
+    Ranged(ProjectileType),
+}
+
+impl State {
+    pub fn check_attack(
+        &self,
+        entity_id: EntityId,
+        direction: Direction,
+    ) -> Result<AttackKind, AttackError> {
+        let entity = (self.entities.get(&entity_id)).ok_or(AttackError::NoEntity)?;
+        if entity.is_dead() {
+            return Err(AttackError::Dead);
+        }
+
+        let weapon = (entity.inventory.equipped.get(&EquipSlot::Weapon))
+            .map(|item| self.get_type(item.type_id))
+            .ok_or(AttackError::NoWeapon)?;
+        let tool_kind = weapon.tool_kind.ok_or(AttackError::NoWeapon)?;
+
+        let tiles = attack_tiles(tool_kind, entity.pos, direction).collect::<Vec<_>>();
+        if !tiles.is_empty() {
+            return Ok(AttackKind::Melee(tiles));
+        }
+
+        (weapon.projectile)
+            .map(AttackKind::Ranged)
+            .ok_or(AttackError::NoProjectile)
+    }
+
+    /// Damages every living entity on the hit tiles and returns them
+    ///
+    /// Ranged weapons shoot a projectile instead, which hits later, so nothing is returned
+    pub fn attack(
+        &mut self,
+        entity_id: EntityId,
+        direction: Direction,
+    ) -> Result<Vec<EntityId>, AttackError> {
+        let damage = self.weapon_damage(entity_id);
+        let tiles = match self.check_attack(entity_id, direction)? {
+            AttackKind::Melee(tiles) => tiles,
+
+            AttackKind::Ranged(projectile_type) => {
+                self.shoot(entity_id, direction, projectile_type, damage);
+                return Ok(Vec::new());
+            }
+        };
+
+        let targets = (tiles.iter())
+            .flat_map(|&pos| self.entities_at(pos).iter().copied())
+            .filter(|&target_id| target_id != entity_id && self.entities[&target_id].is_blocking())
+            .collect::<Vec<_>>();
+
+        for &target_id in &targets {
+            // invulnerable targets are simply not hurt
+            let _ = self.damage(target_id, damage, Some(entity_id));
+        }
+
+        Ok(targets)
+    }
+
+    fn weapon_damage(&self, entity_id: EntityId) -> u32 {
+        (self.inventory(entity_id).ok())
+            .and_then(|inventory| inventory.equipped.get(&EquipSlot::Weapon))
+            .map_or(0, |item| self.get_type(item.type_id).stats.damage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntityTypeId, ItemType, PLAYER_ENTITY_TYPE_ID, vec2};
+    use std::collections::HashSet;
+
+    fn tiles(tool_kind: ToolKind, direction: Direction) -> HashSet<(i32, i32)> {
+        attack_tiles(tool_kind, vec2(0, 0), direction)
+            .map(|pos| (pos.x, pos.y))
+            .collect()
+    }
+
+    fn set(tiles: &[(i32, i32)]) -> HashSet<(i32, i32)> {
+        tiles.iter().copied().collect()
+    }
+
+    #[test]
+    fn dagger_hits_one_tile() {
+        assert_eq!(tiles(ToolKind::Dagger, Direction::Up), set(&[(0, -1)]));
+        assert_eq!(tiles(ToolKind::Dagger, Direction::Left), set(&[(-1, 0)]));
+    }
+
+    #[test]
+    fn sword_hits_an_arc() {
+        assert_eq!(
+            tiles(ToolKind::Sword, Direction::Up),
+            set(&[(-1, -1), (0, -1), (1, -1)])
+        );
+        assert_eq!(
+            tiles(ToolKind::Sword, Direction::Right),
+            set(&[(1, -1), (1, 0), (1, 1)])
+        );
+    }
+
+    #[test]
+    fn scythe_sweeps_the_sides() {
+        assert_eq!(
+            tiles(ToolKind::Scythe, Direction::Down),
+            set(&[(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)])
+        );
+    }
+
+    #[test]
+    fn hammer_hits_two_tiles_ahead() {
+        assert_eq!(
+            tiles(ToolKind::Hammer, Direction::Left),
+            set(&[(-1, 0), (-2, 0)])
+        );
+    }
+
+    #[test]
+    fn whip_hits_a_long_line() {
+        assert_eq!(
+            tiles(ToolKind::Whip, Direction::Down),
+            set(&[(0, 1), (0, 2), (0, 3), (0, 4)])
+        );
+    }
+
+    #[test]
+    fn ranged_weapons_have_no_melee_shape() {
+        assert!(tiles(ToolKind::Bow, Direction::Up).is_empty());
+        assert!(tiles(ToolKind::Crossbow, Direction::Up).is_empty());
+    }
+
+    #[test]
+    fn digging_tools_hit_one_tile() {
+        for tool_kind in [ToolKind::Pickaxe, ToolKind::Axe, ToolKind::Shovel] {
+            assert_eq!(tiles(tool_kind, Direction::Right), set(&[(1, 0)]));
+        }
+    }
+
+    fn armed_state(tool_kind: ToolKind) -> (State, EntityId, EntityTypeId) {
+        let mut state = State::default();
+        let weapon = state.insert_type(ItemType::new("weapon").tool(tool_kind).damage(2));
+        let dummy = state.insert_type(crate::EntityType::new("dummy").health(3));
+
+        let attacker = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+        state.give_item(attacker, weapon.instance()).unwrap();
+
+        (state, attacker, dummy)
+    }
+
+    #[test]
+    fn sword_damages_every_entity_in_the_arc() {
+        let (mut state, attacker, dummy) = armed_state(ToolKind::Sword);
+        let hit = [vec2(-1, -1), vec2(0, -1), vec2(1, -1)]
+            .map(|pos| state.spawn(dummy.instance(pos)).unwrap());
+        let missed = state.spawn(dummy.instance(vec2(0, -2))).unwrap();
+
+        let mut targets = state.attack(attacker, Direction::Up).unwrap();
+        targets.sort();
+        assert_eq!(targets, hit);
+
+        for entity_id in hit {
+            assert_eq!(state.entities()[&entity_id].health.unwrap().current, 1);
+        }
+        assert_eq!(state.entities()[&missed].health.unwrap().current, 3);
+    }
+
+    #[test]
+    fn killed_entities_are_despawned() {
+        let (mut state, attacker, dummy) = armed_state(ToolKind::Dagger);
+        let target = state.spawn(dummy.instance(vec2(1, 0))).unwrap();
+
+        state.attack(attacker, Direction::Right).unwrap();
+        state.attack(attacker, Direction::Right).unwrap();
+        assert!(!state.entities().contains_key(&target));
+    }
+
+    #[test]
+    fn ranged_weapons_need_a_projectile() {
+        let (mut state, attacker, _) = armed_state(ToolKind::Bow);
+        assert_eq!(
+            state.attack(attacker, Direction::Up),
+            Err(AttackError::NoProjectile)
+        );
+    }
+
+    #[test]
+    fn unarmed_entities_cant_attack() {
+        let mut state = State::default();
+        let attacker = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+        assert_eq!(
+            state.attack(attacker, Direction::Up),
+            Err(AttackError::NoWeapon)
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{ObjectType, State, Vec2, vec2};
+use std::collections::HashSet;
+
+// rounds halves away from zero, `b` has to be positive
+const fn div_round(a: i32, b: i32) -> i32 {
+    (2 * a + b * a.signum()) / (2 * b)
+}
+
+// every tile from `from` to `to`, both included
+fn line(from: Vec2, to: Vec2) -> impl Iterator<Item = Vec2> {
+    let delta = to - from;
+    let steps = delta.x.abs().max(delta.y.abs()).max(1);
+    (0..=steps).map(move |step| {
+        from + vec2(
+            div_round(delta.x * step, steps),
+            div_round(delta.y * step, steps),
+        )
+    })
+}
+
+impl State {
+    pub fn is_opaque(&self, pos: Vec2) -> bool {
+        (self.walls.get(&(pos.x, pos.y)))
+            .is_some_and(|wall| !self.get_type(wall.type_id).transparent)
+    }
+
+    /// Tiles seen from `origin` at most `radius` tiles away, opaque walls are seen but hide what's behind them
+    pub fn field_of_view(&self, origin: Vec2, radius: i32) -> HashSet<(i32, i32)> {
+        let in_radius = |pos: Vec2| {
+            let offset = pos - origin;
+            offset.x * offset.x + offset.y * offset.y <= radius * radius + radius
+        };
+
+        // a ray to every tile on the border of the square around `origin`
+        let border = (-radius..=radius).flat_map(|i| {
+            [
+                vec2(i, -radius),
+                vec2(i, radius),
+                vec2(-radius, i),
+                vec2(radius, i),
+            ]
+        });
+
+        let mut res = HashSet::from([(origin.x, origin.y)]);
+        for target in border {
+            for pos in line(origin, origin + target).skip(1) {
+                if !in_radius(pos) {
+                    break;
+                }
+
+                res.insert((pos.x, pos.y));
+                if self.is_opaque(pos) {
+                    break;
+                }
+            }
+        }
+
+        // rays miss some of the walls along the sides of a room
+        let seen_walls = (res.iter())
+            .map(|&(x, y)| vec2(x, y))
+            .filter(|&pos| !self.is_opaque(pos))
+            .flat_map(|pos| (-1..=1).flat_map(move |x| (-1..=1).map(move |y| pos + vec2(x, y))))
+            .filter(|&pos| in_radius(pos) && self.is_opaque(pos))
+            .map(|pos| (pos.x, pos.y))
+            .collect::<Vec<_>>();
+        res.extend(seen_walls);
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn corridor(wall: WallType) -> State {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let wall = state.insert_type(wall);
+
+        state.place_rect((0, 0), (9, 0), grass.instance());
+        state.place((4, 0), wall.instance());
+        state
+    }
+
+    #[test]
+    fn opaque_walls_are_seen_but_hide_what_is_behind() {
+        let state = corridor(WallType::new("stone"));
+        let seen = state.field_of_view(vec2(0, 0), 8);
+
+        assert!(seen.contains(&(3, 0)));
+        assert!(seen.contains(&(4, 0)));
+        assert!(!seen.contains(&(5, 0)));
+    }
+
+    #[test]
+    fn transparent_walls_are_seen_through() {
+        let state = corridor(WallType::new("glass").transparent());
+        let seen = state.field_of_view(vec2(0, 0), 8);
+
+        assert!(seen.contains(&(5, 0)));
+        assert!(seen.contains(&(8, 0)));
+        assert!(!seen.contains(&(9, 0)));
+    }
+
+    #[test]
+    fn rooms_show_all_of_their_walls() {
+        let mut state = State::default();
+        let stone = state.insert_type(WallType::new("stone"));
+        state.place_frame((-3, -3), (3, 3), stone.instance());
+
+        let seen = state.field_of_view(vec2(0, 0), 8);
+        for x in -3..=3 {
+            for y in -3..=3 {
+                assert!(seen.contains(&(x, y)));
+            }
+        }
+        assert!(!seen.contains(&(4, 0)));
+    }
+}
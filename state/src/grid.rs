@@ -0,0 +1,298 @@
+use crate::Vec2;
+use std::{
+    collections::{HashMap, hash_map},
+    hash::{BuildHasherDefault, Hasher},
+};
+
+const CHUNK_SHIFT: i32 = 4;
+pub const CHUNK_SIZE: i32 = 1 << CHUNK_SHIFT;
+const CHUNK_AREA: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Debug, Clone)]
+struct Chunk<T> {
+    // row by row
+    tiles: Box<[Option<T>; CHUNK_AREA]>,
+    // a bit for every tile that's there, so that iterating skips the empty ones quickly
+    occupied: [u64; CHUNK_AREA / 64],
+}
+
+impl<T> Chunk<T> {
+    fn new() -> Self {
+        Self {
+            tiles: Box::new([const { None }; CHUNK_AREA]),
+            occupied: [0; CHUNK_AREA / 64],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.occupied.iter().all(|&bits| bits == 0)
+    }
+}
+
+// FxHash, chunk keys are few and don't need the DoS resistance of the default hasher
+#[derive(Default)]
+struct ChunkHasher(u64);
+
+impl Hasher for ChunkHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(u64::from(byte));
+        }
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u64(u64::from(n as u32));
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
+
+type Chunks<T> = HashMap<(i32, i32), Chunk<T>, BuildHasherDefault<ChunkHasher>>;
+
+struct Iter<'a, T> {
+    chunks: hash_map::Iter<'a, (i32, i32), Chunk<T>>,
+    // `None` before the first chunk
+    chunk: Option<((i32, i32), &'a Chunk<T>)>,
+    word: usize,
+    // the current word of `Chunk::occupied` with the bits of already returned tiles cleared
+    bits: u64,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = ((i32, i32), &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(((chunk_x, chunk_y), chunk)) = self.chunk {
+                if self.bits != 0 {
+                    let index = self.word * 64 + self.bits.trailing_zeros() as usize;
+                    self.bits &= self.bits - 1;
+                    self.len -= 1;
+
+                    let x = (chunk_x << CHUNK_SHIFT) | (index as i32 & (CHUNK_SIZE - 1));
+                    let y = (chunk_y << CHUNK_SHIFT) | (index as i32 >> CHUNK_SHIFT);
+                    return Some(((x, y), chunk.tiles[index % CHUNK_AREA].as_ref()?));
+                }
+
+                if self.word + 1 < chunk.occupied.len() {
+                    self.word += 1;
+                    self.bits = chunk.occupied[self.word];
+                    continue;
+                }
+            }
+
+            let (&key, chunk) = self.chunks.next()?;
+            self.chunk = Some((key, chunk));
+            self.word = 0;
+            self.bits = chunk.occupied[0];
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+// shifting rounds towards negative infinity, unlike dividing
+const fn chunk_key(x: i32, y: i32) -> (i32, i32) {
+    (x >> CHUNK_SHIFT, y >> CHUNK_SHIFT)
+}
+
+const fn tile_index(x: i32, y: i32) -> usize {
+    (((y & (CHUNK_SIZE - 1)) << CHUNK_SHIFT) | (x & (CHUNK_SIZE - 1))) as usize
+}
+
+/// Tiles stored in dense `CHUNK_SIZE`x`CHUNK_SIZE` chunks, which are dropped once they're empty
+#[derive(Debug, Clone)]
+pub struct ChunkedGrid<T> {
+    chunks: Chunks<T>,
+    len: usize,
+}
+
+impl<T> Default for ChunkedGrid<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Chunks::default(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> ChunkedGrid<T> {
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, &(x, y): &(i32, i32)) -> Option<&T> {
+        let chunk = self.chunks.get(&chunk_key(x, y))?;
+        chunk.tiles[tile_index(x, y)].as_ref()
+    }
+
+    pub fn get_mut(&mut self, &(x, y): &(i32, i32)) -> Option<&mut T> {
+        let chunk = self.chunks.get_mut(&chunk_key(x, y))?;
+        chunk.tiles[tile_index(x, y)].as_mut()
+    }
+
+    pub fn contains_key(&self, key: &(i32, i32)) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the tile that was there before
+    pub fn insert(&mut self, (x, y): (i32, i32), tile: T) -> Option<T> {
+        let chunk = (self.chunks.entry(chunk_key(x, y))).or_insert_with(Chunk::new);
+        let index = tile_index(x, y);
+        let res = chunk.tiles[index].replace(tile);
+        if res.is_none() {
+            chunk.occupied[index / 64] |= 1 << (index % 64);
+            self.len += 1;
+        }
+        res
+    }
+
+    pub fn remove(&mut self, &(x, y): &(i32, i32)) -> Option<T> {
+        let key = chunk_key(x, y);
+        let chunk = self.chunks.get_mut(&key)?;
+        let index = tile_index(x, y);
+        let res = chunk.tiles[index].take()?;
+
+        chunk.occupied[index / 64] &= !(1 << (index % 64));
+        self.len -= 1;
+        if chunk.is_empty() {
+            self.chunks.remove(&key);
+        }
+        Some(res)
+    }
+
+    /// In no particular order between chunks
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = ((i32, i32), &T)> {
+        Iter {
+            chunks: self.chunks.iter(),
+            chunk: None,
+            word: 0,
+            bits: 0,
+            len: self.len,
+        }
+    }
+
+    /// Tiles inside the rectangle from `min` to `max`, both included, only looking into the chunks that overlap it
+    pub fn region(
+        &self,
+        min: impl Into<Vec2>,
+        max: impl Into<Vec2>,
+    ) -> impl Iterator<Item = ((i32, i32), &T)> {
+        let min = min.into();
+        let max = max.into();
+        let (min_chunk_x, min_chunk_y) = chunk_key(min.x, min.y);
+        let (max_chunk_x, max_chunk_y) = chunk_key(max.x, max.y);
+
+        // for huge regions it's cheaper to go over the existing chunks instead
+        let overlapping = i64::from(max_chunk_x - min_chunk_x + 1).max(0)
+            * i64::from(max_chunk_y - min_chunk_y + 1).max(0);
+        let mut keys = if overlapping > self.chunks.len() as i64 {
+            (self.chunks.keys())
+                .filter(|&&(chunk_x, chunk_y)| {
+                    (min_chunk_x..=max_chunk_x).contains(&chunk_x)
+                        && (min_chunk_y..=max_chunk_y).contains(&chunk_y)
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        } else {
+            (min_chunk_y..=max_chunk_y)
+                .flat_map(|chunk_y| {
+                    (min_chunk_x..=max_chunk_x).map(move |chunk_x| (chunk_x, chunk_y))
+                })
+                .filter(|key| self.chunks.contains_key(key))
+                .collect::<Vec<_>>()
+        };
+        keys.sort_by_key(|&(chunk_x, chunk_y)| (chunk_y, chunk_x));
+
+        keys.into_iter().flat_map(move |(chunk_x, chunk_y)| {
+            let chunk = &self.chunks[&(chunk_x, chunk_y)];
+            let xs = min.x.max(chunk_x * CHUNK_SIZE)..=max.x.min((chunk_x + 1) * CHUNK_SIZE - 1);
+            let ys = min.y.max(chunk_y * CHUNK_SIZE)..=max.y.min((chunk_y + 1) * CHUNK_SIZE - 1);
+
+            ys.flat_map(move |y| {
+                (xs.clone())
+                    .filter_map(move |x| Some(((x, y), chunk.tiles[tile_index(x, y)].as_ref()?)))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn tiles_are_kept_across_chunks() {
+        let mut grid = ChunkedGrid::default();
+        assert_eq!(grid.insert((-1, -1), 'a'), None);
+        assert_eq!(grid.insert((0, 0), 'b'), None);
+        assert_eq!(grid.insert((CHUNK_SIZE, -CHUNK_SIZE), 'c'), None);
+        assert_eq!(grid.insert((0, 0), 'd'), Some('b'));
+
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid.get(&(-1, -1)), Some(&'a'));
+        assert_eq!(grid.get(&(0, 0)), Some(&'d'));
+        assert_eq!(grid.get(&(CHUNK_SIZE, -CHUNK_SIZE)), Some(&'c'));
+        assert_eq!(grid.get(&(1, 0)), None);
+
+        let mut tiles = grid.iter().collect::<Vec<_>>();
+        tiles.sort_unstable();
+        assert_eq!(tiles, [
+            ((-1, -1), &'a'),
+            ((0, 0), &'d'),
+            ((CHUNK_SIZE, -CHUNK_SIZE), &'c')
+        ]);
+    }
+
+    #[test]
+    fn empty_chunks_are_dropped() {
+        let mut grid = ChunkedGrid::default();
+        grid.insert((3, 3), ());
+        grid.insert((4, 3), ());
+
+        assert_eq!(grid.remove(&(3, 3)), Some(()));
+        assert_eq!(grid.remove(&(3, 3)), None);
+        assert_eq!(grid.chunks.len(), 1);
+
+        grid.remove(&(4, 3));
+        assert!(grid.is_empty());
+        assert!(grid.chunks.is_empty());
+    }
+
+    #[test]
+    fn regions_only_have_tiles_inside_them() {
+        let mut grid = ChunkedGrid::default();
+        for x in -40..40 {
+            for y in -40..40 {
+                grid.insert((x, y), x * y);
+            }
+        }
+
+        let region = grid.region(vec2(-17, 5), vec2(20, 6)).collect::<Vec<_>>();
+        assert_eq!(region.len(), 38 * 2);
+        assert!(region.iter().all(|&((x, y), &tile)| {
+            (-17..=20).contains(&x) && (5..=6).contains(&y) && tile == x * y
+        }));
+
+        assert_eq!(
+            grid.region(vec2(-1000, -1000), vec2(1000, 1000)).count(),
+            grid.len()
+        );
+        assert_eq!(grid.region(vec2(1, 1), vec2(0, 0)).count(), 0);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{Entity, EntityId, ObjectType, PLAYER_ENTITY_TYPE_ID, PluginEvent, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub const fn full(max: u32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub const fn is_dead(self) -> bool {
+        self.current == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageError {
+    NoEntity,
+    Invulnerable,
+    Dead,
+}
+
+impl State {
+    pub fn is_alive(&self, entity_id: EntityId) -> bool {
+        (self.entities.get(&entity_id)).is_some_and(|entity| !entity.is_dead())
+    }
+
+    /// Killed players stay as corpses, other entities are despawned
+    pub fn damage(
+        &mut self,
+        entity_id: EntityId,
+        amount: u32,
+        source: Option<EntityId>,
+    ) -> Result<(), DamageError> {
+        let entity = (self.entities.get_mut(&entity_id)).ok_or(DamageError::NoEntity)?;
+        let health = entity.health.as_mut().ok_or(DamageError::Invulnerable)?;
+        if health.is_dead() {
+            return Err(DamageError::Dead);
+        }
+
+        health.current = health.current.saturating_sub(amount);
+        let died = health.is_dead();
+        let type_id = entity.type_id;
+        self.changes.entities.insert(entity_id);
+
+        self.push_event(PluginEvent::EntityDamaged {
+            entity_id,
+            amount,
+            source,
+        });
+
+        if died {
+            self.push_event(PluginEvent::EntityDied {
+                entity_id,
+                killer: source,
+            });
+
+            if type_id != PLAYER_ENTITY_TYPE_ID {
+                self.despawn(entity_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn heal(&mut self, entity_id: EntityId, amount: u32) -> Result<(), DamageError> {
+        let entity = (self.entities.get_mut(&entity_id)).ok_or(DamageError::NoEntity)?;
+        let health = entity.health.as_mut().ok_or(DamageError::Invulnerable)?;
+        if health.is_dead() {
+            return Err(DamageError::Dead);
+        }
+
+        health.current = (health.current.saturating_add(amount)).min(health.max);
+        self.changes.entities.insert(entity_id);
+        Ok(())
+    }
+
+    // entities spawned without their own health get the one of their type
+    pub(crate) fn init_health(&self, entity: &mut Entity) {
+        if entity.health.is_none() {
+            entity.health = self.get_type(entity.type_id).max_health.map(Health::full);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{EntityId, Item, ObjectType, Place, State, ToolKind};
+use std::collections::HashMap;
+
+pub const INVENTORY_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipSlot {
+    Weapon,
+    DiggingTool,
+    Torch,
+}
+
+#[derive(Debug, Default)]
+pub struct Inventory {
+    pub bag: [Option<Item>; INVENTORY_SIZE],
+    pub equipped: HashMap<EquipSlot, Item>,
+}
+
+impl Inventory {
+    fn free_index(&self) -> Option<usize> {
+        self.bag.iter().position(Option::is_none)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryError {
+    NoEntity,
+    Dead,
+    InvalidSlot,
+    EmptySlot,
+    NotEquippable,
+    Full,
+    NothingToPickUp,
+}
+
+impl State {
+    pub fn inventory(&self, entity_id: EntityId) -> Result<&Inventory, InventoryError> {
+        (self.entities.get(&entity_id))
+            .map(|entity| &entity.inventory)
+            .ok_or(InventoryError::NoEntity)
+    }
+
+    // the dead can't handle their items, but their inventory can still be looked at
+    fn living_inventory(&self, entity_id: EntityId) -> Result<&Inventory, InventoryError> {
+        let entity = (self.entities.get(&entity_id)).ok_or(InventoryError::NoEntity)?;
+        if entity.is_dead() {
+            return Err(InventoryError::Dead);
+        }
+        Ok(&entity.inventory)
+    }
+
+    fn inventory_mut(&mut self, entity_id: EntityId) -> Result<&mut Inventory, InventoryError> {
+        let entity = (self.entities.get_mut(&entity_id)).ok_or(InventoryError::NoEntity)?;
+        self.changes.inventories.insert(entity_id);
+        Ok(&mut entity.inventory)
+    }
+
+    pub fn equipped_tool(&self, entity_id: EntityId, slot: EquipSlot) -> Option<ToolKind> {
+        let item = self.inventory(entity_id).ok()?.equipped.get(&slot)?;
+        self.get_type(item.type_id).tool_kind
+    }
+
+    /// Equips the item if its slot is free, otherwise puts it into the bag or on the ground under the entity if the bag is full
+    pub fn give_item(&mut self, entity_id: EntityId, item: Item) -> Result<(), InventoryError> {
+        self.living_inventory(entity_id)?;
+        let slot = self.get_type(item.type_id).slot;
+        let inventory = self.inventory_mut(entity_id)?;
+
+        if let Some(slot) = slot
+            && !inventory.equipped.contains_key(&slot)
+        {
+            inventory.equipped.insert(slot, item);
+            return Ok(());
+        }
+
+        match inventory.free_index() {
+            Some(index) => inventory.bag[index] = Some(item),
+
+            None => {
+                let pos = self.entities[&entity_id].pos;
+                self.place(pos, item);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The previously equipped item takes the place of the equipped one in the bag
+    pub fn equip(&mut self, entity_id: EntityId, index: usize) -> Result<(), InventoryError> {
+        let item = (self.living_inventory(entity_id)?.bag.get(index))
+            .ok_or(InventoryError::InvalidSlot)?;
+        let item = item.as_ref().ok_or(InventoryError::EmptySlot)?;
+        let slot = (self.get_type(item.type_id).slot).ok_or(InventoryError::NotEquippable)?;
+
+        let inventory = self.inventory_mut(entity_id)?;
+        let item = inventory.bag[index]
+            .take()
+            .ok_or(InventoryError::EmptySlot)?;
+        inventory.bag[index] = inventory.equipped.insert(slot, item);
+        Ok(())
+    }
+
+    pub fn unequip(&mut self, entity_id: EntityId, slot: EquipSlot) -> Result<(), InventoryError> {
+        let inventory = self.living_inventory(entity_id)?;
+        if !inventory.equipped.contains_key(&slot) {
+            return Err(InventoryError::EmptySlot);
+        }
+        let index = inventory.free_index().ok_or(InventoryError::Full)?;
+
+        let inventory = self.inventory_mut(entity_id)?;
+        inventory.bag[index] = inventory.equipped.remove(&slot);
+        Ok(())
+    }
+
+    pub fn swap_items(
+        &mut self,
+        entity_id: EntityId,
+        index: usize,
+        other_index: usize,
+    ) -> Result<(), InventoryError> {
+        self.living_inventory(entity_id)?;
+        if index >= INVENTORY_SIZE || other_index >= INVENTORY_SIZE {
+            return Err(InventoryError::InvalidSlot);
+        }
+
+        self.inventory_mut(entity_id)?.bag.swap(index, other_index);
+        Ok(())
+    }
+
+    pub fn drop_item(&mut self, entity_id: EntityId, index: usize) -> Result<(), InventoryError> {
+        let item = (self.living_inventory(entity_id)?.bag.get(index))
+            .ok_or(InventoryError::InvalidSlot)?;
+        if item.is_none() {
+            return Err(InventoryError::EmptySlot);
+        }
+
+        let pos = self.entities[&entity_id].pos;
+        let item = self.inventory_mut(entity_id)?.bag[index].take();
+        if let Some(item) = item {
+            self.place(pos, item);
+        }
+        Ok(())
+    }
+
+    /// Takes the topmost item on the entity's tile
+    pub fn pick_up(&mut self, entity_id: EntityId) -> Result<(), InventoryError> {
+        let inventory = self.living_inventory(entity_id)?;
+        let pos = self.entities[&entity_id].pos;
+        let key = (pos.x, pos.y);
+
+        let item = (self.items.get(&key))
+            .and_then(|items| items.last())
+            .ok_or(InventoryError::NothingToPickUp)?;
+
+        let slot_is_free = (self.get_type(item.type_id).slot)
+            .is_some_and(|slot| !inventory.equipped.contains_key(&slot));
+        if !slot_is_free && inventory.free_index().is_none() {
+            return Err(InventoryError::Full);
+        }
+
+        let Some(items) = self.items.get_mut(&key) else {
+            return Err(InventoryError::NothingToPickUp);
+        };
+        let item = items.pop().ok_or(InventoryError::NothingToPickUp)?;
+        if items.is_empty() {
+            self.items.remove(&key);
+        }
+        self.changes.items.insert(key);
+
+        self.give_item(entity_id, item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    struct Camp {
+        state: State,
+        player: EntityId,
+        sword: ItemTypeId,
+        rock: ItemTypeId,
+    }
+
+    fn camp() -> Camp {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let sword = state.insert_type(ItemType::new("sword").tool(ToolKind::Sword));
+        let rock = state.insert_type(ItemType::new("rock"));
+
+        state.place((0, 0), grass.instance());
+        let player = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+
+        Camp {
+            state,
+            player,
+            sword,
+            rock,
+        }
+    }
+
+    #[test]
+    fn slots_are_checked() {
+        let Camp {
+            mut state,
+            player,
+            rock,
+            ..
+        } = camp();
+        state.give_item(player, rock.instance()).unwrap();
+
+        let invalid = INVENTORY_SIZE;
+        assert_eq!(
+            state.equip(player, invalid),
+            Err(InventoryError::InvalidSlot)
+        );
+        assert_eq!(
+            state.drop_item(player, invalid),
+            Err(InventoryError::InvalidSlot)
+        );
+        assert_eq!(
+            state.swap_items(player, 0, invalid),
+            Err(InventoryError::InvalidSlot)
+        );
+
+        assert_eq!(state.equip(player, 1), Err(InventoryError::EmptySlot));
+        assert_eq!(state.drop_item(player, 1), Err(InventoryError::EmptySlot));
+        assert_eq!(
+            state.unequip(player, EquipSlot::Weapon),
+            Err(InventoryError::EmptySlot)
+        );
+        assert_eq!(state.equip(player, 0), Err(InventoryError::NotEquippable));
+    }
+
+    #[test]
+    fn full_bags_take_nothing_more() {
+        let Camp {
+            mut state,
+            player,
+            sword,
+            rock,
+        } = camp();
+        state.give_item(player, sword.instance()).unwrap();
+        for _ in 0..INVENTORY_SIZE {
+            state.give_item(player, rock.instance()).unwrap();
+        }
+        assert_eq!(state.pick_up(player), Err(InventoryError::NothingToPickUp));
+
+        // overflowing items land on the ground and can't be picked up again
+        state.give_item(player, rock.instance()).unwrap();
+        assert_eq!(state.items()[&(0, 0)].len(), 1);
+        assert_eq!(state.pick_up(player), Err(InventoryError::Full));
+        assert_eq!(
+            state.unequip(player, EquipSlot::Weapon),
+            Err(InventoryError::Full)
+        );
+    }
+
+    #[test]
+    fn the_dead_cant_handle_items() {
+        let Camp {
+            mut state,
+            player,
+            sword,
+            rock,
+        } = camp();
+        state.give_item(player, sword.instance()).unwrap();
+        state.give_item(player, rock.instance()).unwrap();
+        state.place((0, 0), rock.instance());
+        state.damage(player, u32::MAX, None).unwrap();
+
+        assert_eq!(
+            state.give_item(player, rock.instance()),
+            Err(InventoryError::Dead)
+        );
+        assert_eq!(state.equip(player, 0), Err(InventoryError::Dead));
+        assert_eq!(
+            state.unequip(player, EquipSlot::Weapon),
+            Err(InventoryError::Dead)
+        );
+        assert_eq!(state.swap_items(player, 0, 1), Err(InventoryError::Dead));
+        assert_eq!(state.drop_item(player, 0), Err(InventoryError::Dead));
+        assert_eq!(state.pick_up(player), Err(InventoryError::Dead));
+
+        let inventory = state.inventory(player).unwrap();
+        assert!(inventory.equipped.contains_key(&EquipSlot::Weapon));
+        assert!(inventory.bag[0].is_some());
+        assert_eq!(state.items()[&(0, 0)].len(), 1);
+    }
+}
@@ -1,5 +1,32 @@
-use sfml::system::Vector2i as Vec2;
-use std::{collections::HashMap, fmt::Debug};
+pub use sfml::system::Vector2i as Vec2;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    mem::take,
+};
+
+mod ai;
+mod attack;
+mod fov;
+mod grid;
+mod health;
+mod inventory;
+mod light;
+mod loot;
+mod occupancy;
+mod pathfinding;
+mod projectile;
+mod rng;
+
+pub use ai::*;
+pub use attack::*;
+pub use grid::*;
+pub use health::*;
+pub use inventory::*;
+pub use light::*;
+pub use loot::*;
+pub use projectile::*;
+pub use rng::*;
 
 pub fn vec2(x: i32, y: i32) -> Vec2 {
     Vec2::new(x, y)
@@ -13,12 +40,41 @@ pub struct FloorTypeId(usize);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WallTypeId(usize);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DecorationTypeId(usize);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EntityTypeId(usize);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemTypeId(usize);
+
 ////////////////////////////////////////////////////////////
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Self; 4] = [Self::Up, Self::Down, Self::Left, Self::Right];
+
+    pub const fn offset(self) -> Vec2 {
+        match self {
+            Self::Up => Vec2::new(0, -1),
+            Self::Down => Vec2::new(0, 1),
+            Self::Left => Vec2::new(-1, 0),
+            Self::Right => Vec2::new(1, 0),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ToolKind {
     Sword,
     Dagger,
@@ -33,12 +89,22 @@ pub enum ToolKind {
     Shovel,
 }
 
+impl ToolKind {
+    pub const fn equip_slot(self) -> EquipSlot {
+        match self {
+            Self::Pickaxe | Self::Axe | Self::Shovel => EquipSlot::DiggingTool,
+            _ => EquipSlot::Weapon,
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////
 
 #[derive(Debug)]
 pub struct FloorType {
     pub asset: String,
     pub walkable: bool,
+    pub light: Option<Light>,
 }
 
 impl FloorType {
@@ -46,6 +112,7 @@ impl FloorType {
         Self {
             asset: asset.into(),
             walkable: true,
+            light: None,
         }
     }
 
@@ -53,6 +120,11 @@ impl FloorType {
         self.walkable = false;
         self
     }
+
+    pub fn light(mut self, radius: i32, brightness: u8) -> Self {
+        self.light = Some(Light { radius, brightness });
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -78,6 +150,11 @@ pub struct Breakable {
 pub struct WallType {
     pub asset: String,
     pub breakable: Option<Breakable>,
+    // broken by any projectile hitting it
+    pub shatters: bool,
+    // otherwise it blocks the view
+    pub transparent: bool,
+    pub light: Option<Light>,
 }
 
 impl WallType {
@@ -85,6 +162,9 @@ impl WallType {
         Self {
             asset: asset.into(),
             breakable: None,
+            shatters: false,
+            transparent: false,
+            light: None,
         }
     }
 
@@ -92,16 +172,99 @@ impl WallType {
         self.breakable = Some(Breakable { tool_kind, hits });
         self
     }
+
+    pub fn shatters(mut self) -> Self {
+        self.shatters = true;
+        self
+    }
+
+    pub fn transparent(mut self) -> Self {
+        self.transparent = true;
+        self
+    }
+
+    pub fn light(mut self, radius: i32, brightness: u8) -> Self {
+        self.light = Some(Light { radius, brightness });
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Wall {
     pub type_id: WallTypeId,
+    // hits taken so far, compared against `Breakable::hits`
+    pub damage: usize,
 }
 
 impl WallTypeId {
     pub fn instance(self) -> Wall {
-        Wall { type_id: self }
+        Wall {
+            type_id: self,
+            damage: 0,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct DecorationType {
+    pub asset: String,
+    pub solid: bool,
+    pub interactable: bool,
+    pub light: Option<Light>,
+}
+
+impl DecorationType {
+    pub fn new(asset: impl Into<String>) -> Self {
+        Self {
+            asset: asset.into(),
+            solid: false,
+            interactable: false,
+            light: None,
+        }
+    }
+
+    pub fn solid(mut self) -> Self {
+        self.solid = true;
+        self
+    }
+
+    pub fn interactable(mut self) -> Self {
+        self.interactable = true;
+        self
+    }
+
+    pub fn light(mut self, radius: i32, brightness: u8) -> Self {
+        self.light = Some(Light { radius, brightness });
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Decoration {
+    pub type_id: DecorationTypeId,
+    pub container: Option<Container>,
+}
+
+impl DecorationTypeId {
+    pub fn instance(self) -> Decoration {
+        Decoration {
+            type_id: self,
+            container: None,
+        }
+    }
+}
+
+impl Decoration {
+    pub fn item(mut self, item: Item) -> Self {
+        self.container.get_or_insert_default().items.push(item);
+        self
+    }
+
+    pub fn loot_table(mut self, loot_table: LootTable) -> Self {
+        self.container.get_or_insert_default().loot_table = Some(loot_table);
+        self
     }
 }
 
@@ -110,22 +273,52 @@ impl WallTypeId {
 #[derive(Debug)]
 pub struct EntityType {
     pub asset: String,
+    // `None` for invulnerable entities
+    pub max_health: Option<u32>,
+    pub behaviour: Option<Box<dyn Behaviour>>,
+    pub light: Option<Light>,
 }
 
 impl EntityType {
     pub fn new(asset: impl Into<String>) -> Self {
         Self {
             asset: asset.into(),
+            max_health: None,
+            behaviour: None,
+            light: None,
         }
     }
+
+    pub fn health(mut self, max_health: u32) -> Self {
+        self.max_health = Some(max_health);
+        self
+    }
+
+    pub fn behaviour(mut self, behaviour: impl Behaviour + 'static) -> Self {
+        self.behaviour = Some(Box::new(behaviour));
+        self
+    }
+
+    pub fn light(mut self, radius: i32, brightness: u8) -> Self {
+        self.light = Some(Light { radius, brightness });
+        self
+    }
 }
 
+/// Indices of despawned entities are reused with the next generation, so that stale ids never refer to newer entities
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct EntityId(usize);
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
 
-impl From<EntityId> for usize {
-    fn from(id: EntityId) -> Self {
-        id.0
+impl EntityId {
+    pub const fn index(self) -> u32 {
+        self.index
+    }
+
+    pub const fn generation(self) -> u32 {
+        self.generation
     }
 }
 
@@ -134,6 +327,10 @@ pub struct Entity {
     pub type_id: EntityTypeId,
     pub pos: Vec2,
     pub asset: Option<String>,
+    pub inventory: Inventory,
+    // taken from `EntityType::max_health` on spawn unless set
+    pub health: Option<Health>,
+    pub projectile: Option<Projectile>,
 }
 
 impl EntityTypeId {
@@ -142,35 +339,220 @@ impl EntityTypeId {
             type_id: self,
             pos,
             asset: None,
+            inventory: Inventory::default(),
+            health: None,
+            projectile: None,
         }
     }
 }
 
 impl Entity {
+    pub fn health(mut self, max_health: u32) -> Self {
+        self.health = Some(Health::full(max_health));
+        self
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health.is_some_and(Health::is_dead)
+    }
+
+    /// Corpses and projectiles can be walked over
+    pub fn is_blocking(&self) -> bool {
+        !self.is_dead() && self.projectile.is_none()
+    }
+
     pub fn asset(mut self, asset: impl Into<String>) -> Self {
         self.asset = Some(asset.into());
         self
     }
 }
 
+/// Where and with which skin the server spawns a player that has just joined
+#[derive(Debug)]
+pub struct PlayerSpawn {
+    pub pos: Vec2,
+    pub asset: String,
+    // given to the player right after spawning
+    pub items: Vec<Item>,
+}
+
+////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ItemStats {
+    pub damage: u32,
+}
+
+#[derive(Debug)]
+pub struct ItemType {
+    pub asset: String,
+    pub tool_kind: Option<ToolKind>,
+    pub slot: Option<EquipSlot>,
+    pub stats: ItemStats,
+    // `None` for everything but ranged weapons
+    pub projectile: Option<ProjectileType>,
+    // given off both when equipped and when lying on the ground
+    pub light: Option<Light>,
+}
+
+impl ItemType {
+    pub fn new(asset: impl Into<String>) -> Self {
+        Self {
+            asset: asset.into(),
+            tool_kind: None,
+            slot: None,
+            stats: ItemStats::default(),
+            projectile: None,
+            light: None,
+        }
+    }
+
+    pub fn tool(mut self, tool_kind: ToolKind) -> Self {
+        self.tool_kind = Some(tool_kind);
+        self.slot = Some(tool_kind.equip_slot());
+        self
+    }
+
+    pub fn equippable(mut self, slot: EquipSlot) -> Self {
+        self.slot = Some(slot);
+        self
+    }
+
+    pub fn damage(mut self, damage: u32) -> Self {
+        self.stats.damage = damage;
+        self
+    }
+
+    pub fn shoots(mut self, type_id: EntityTypeId, speed: u32) -> Self {
+        self.projectile = Some(ProjectileType { type_id, speed });
+        self
+    }
+
+    pub fn light(mut self, radius: i32, brightness: u8) -> Self {
+        self.light = Some(Light { radius, brightness });
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub type_id: ItemTypeId,
+}
+
+impl ItemTypeId {
+    pub fn instance(self) -> Item {
+        Item { type_id: self }
+    }
+}
+
+////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub enum PluginEvent {
+    PlayerJoined {
+        entity_id: EntityId,
+        username: String,
+    },
+    PlayerLeft {
+        entity_id: EntityId,
+        username: String,
+    },
+    Tick,
+    Beat {
+        number: u64,
+    },
+    EntityMoved {
+        entity_id: EntityId,
+        from: Vec2,
+        to: Vec2,
+    },
+    WallHit {
+        entity_id: EntityId,
+        pos: Vec2,
+    },
+    WallBroken {
+        entity_id: EntityId,
+        pos: Vec2,
+    },
+    DecorationInteracted {
+        entity_id: EntityId,
+        pos: Vec2,
+    },
+    EntityDamaged {
+        entity_id: EntityId,
+        amount: u32,
+        source: Option<EntityId>,
+    },
+    EntityDied {
+        entity_id: EntityId,
+        killer: Option<EntityId>,
+    },
+    Custom {
+        entity_id: EntityId,
+        message: String,
+    },
+}
+
 ////////////////////////////////////////////////////////////
 
 pub const PLAYER_ENTITY_TYPE_ID: EntityTypeId = EntityTypeId(0);
+pub const PLAYER_MAX_HEALTH: u32 = 10;
+
+// plugins can reseed with `*state.rng() = Rng::new(seed)`
+const DEFAULT_SEED: u64 = 0;
+
+// only keys are recorded: whether an object was changed or removed is looked up in `State` afterwards
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub floors: HashSet<(i32, i32)>,
+    pub walls: HashSet<(i32, i32)>,
+    pub decorations: HashSet<(i32, i32)>,
+    pub entities: HashSet<EntityId>,
+    pub items: HashSet<(i32, i32)>,
+    pub inventories: HashSet<EntityId>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.floors.is_empty()
+            && self.walls.is_empty()
+            && self.decorations.is_empty()
+            && self.entities.is_empty()
+            && self.items.is_empty()
+            && self.inventories.is_empty()
+    }
+}
 
 #[derive(Debug)]
 pub struct State {
     next_floor_type_id: FloorTypeId,
     next_wall_type_id: WallTypeId,
+    next_decoration_type_id: DecorationTypeId,
     next_entity_type_id: EntityTypeId,
-    next_entity_id: EntityId,
+    next_item_type_id: ItemTypeId,
+    // the current generation of every entity index
+    entity_generations: Vec<u32>,
+    free_entity_indices: Vec<u32>,
 
     floor_types: HashMap<FloorTypeId, FloorType>,
     wall_types: HashMap<WallTypeId, WallType>,
+    decoration_types: HashMap<DecorationTypeId, DecorationType>,
     entity_types: HashMap<EntityTypeId, EntityType>,
+    item_types: HashMap<ItemTypeId, ItemType>,
 
-    floors: HashMap<(i32, i32), Floor>,
-    walls: HashMap<(i32, i32), Wall>,
+    floors: ChunkedGrid<Floor>,
+    walls: ChunkedGrid<Wall>,
+    decorations: HashMap<(i32, i32), Decoration>,
     entities: HashMap<EntityId, Entity>,
+    // ids of the entities standing on every tile, kept in sync with `Entity::pos`
+    occupancy: ChunkedGrid<Vec<EntityId>>,
+    one_blocker_per_tile: bool,
+    // items lying on the ground, the last one is on top
+    items: HashMap<(i32, i32), Vec<Item>>,
+
+    rng: Rng,
+    changes: Changes,
+    events: Vec<PluginEvent>,
 }
 
 impl Default for State {
@@ -178,33 +560,89 @@ impl Default for State {
         Self {
             next_floor_type_id: FloorTypeId(0),
             next_wall_type_id: WallTypeId(0),
+            next_decoration_type_id: DecorationTypeId(0),
             next_entity_type_id: EntityTypeId(1),
-            next_entity_id: EntityId(0),
+            next_item_type_id: ItemTypeId(0),
+            entity_generations: Vec::default(),
+            free_entity_indices: Vec::default(),
 
             floor_types: HashMap::default(),
             wall_types: HashMap::default(),
-            entity_types: HashMap::from([(PLAYER_ENTITY_TYPE_ID, EntityType::new(""))]),
+            decoration_types: HashMap::default(),
+            entity_types: HashMap::from([(
+                PLAYER_ENTITY_TYPE_ID,
+                EntityType::new("").health(PLAYER_MAX_HEALTH),
+            )]),
+            item_types: HashMap::default(),
 
-            floors: HashMap::default(),
-            walls: HashMap::default(),
+            floors: ChunkedGrid::default(),
+            walls: ChunkedGrid::default(),
+            decorations: HashMap::default(),
             entities: HashMap::default(),
+            occupancy: ChunkedGrid::default(),
+            one_blocker_per_tile: true,
+            items: HashMap::default(),
+
+            rng: Rng::new(DEFAULT_SEED),
+            changes: Changes::default(),
+            events: Vec::default(),
         }
     }
 }
 
 impl State {
-    pub fn floors(&self) -> &HashMap<(i32, i32), Floor> {
+    pub fn floor_types(&self) -> &HashMap<FloorTypeId, FloorType> {
+        &self.floor_types
+    }
+
+    pub fn wall_types(&self) -> &HashMap<WallTypeId, WallType> {
+        &self.wall_types
+    }
+
+    pub fn decoration_types(&self) -> &HashMap<DecorationTypeId, DecorationType> {
+        &self.decoration_types
+    }
+
+    pub fn entity_types(&self) -> &HashMap<EntityTypeId, EntityType> {
+        &self.entity_types
+    }
+
+    pub fn item_types(&self) -> &HashMap<ItemTypeId, ItemType> {
+        &self.item_types
+    }
+
+    pub fn floors(&self) -> &ChunkedGrid<Floor> {
         &self.floors
     }
 
-    pub fn walls(&self) -> &HashMap<(i32, i32), Wall> {
+    pub fn walls(&self) -> &ChunkedGrid<Wall> {
         &self.walls
     }
 
+    pub fn decorations(&self) -> &HashMap<(i32, i32), Decoration> {
+        &self.decorations
+    }
+
     pub fn entities(&self) -> &HashMap<EntityId, Entity> {
         &self.entities
     }
 
+    pub fn items(&self) -> &HashMap<(i32, i32), Vec<Item>> {
+        &self.items
+    }
+
+    pub fn take_changes(&mut self) -> Changes {
+        take(&mut self.changes)
+    }
+
+    pub fn push_event(&mut self, event: PluginEvent) {
+        self.events.push(event);
+    }
+
+    pub fn take_events(&mut self) -> Vec<PluginEvent> {
+        take(&mut self.events)
+    }
+
     pub fn player_entity_ids(&self) -> impl Iterator<Item = EntityId> + use<'_> {
         self.entities.iter().filter_map(|(&entity_id, entity)| {
             (entity.type_id == PLAYER_ENTITY_TYPE_ID).then_some(entity_id)
@@ -243,6 +681,19 @@ impl ObjectType<WallType, WallTypeId> for State {
     }
 }
 
+impl ObjectType<DecorationType, DecorationTypeId> for State {
+    fn insert_type(&mut self, value: DecorationType) -> DecorationTypeId {
+        let res = self.next_decoration_type_id;
+        self.next_decoration_type_id.0 += 1;
+        self.decoration_types.insert(res, value);
+        res
+    }
+
+    fn get_type(&self, type_id: DecorationTypeId) -> &DecorationType {
+        &self.decoration_types[&type_id]
+    }
+}
+
 impl ObjectType<EntityType, EntityTypeId> for State {
     fn insert_type(&mut self, value: EntityType) -> EntityTypeId {
         let res = self.next_entity_type_id;
@@ -256,6 +707,19 @@ impl ObjectType<EntityType, EntityTypeId> for State {
     }
 }
 
+impl ObjectType<ItemType, ItemTypeId> for State {
+    fn insert_type(&mut self, value: ItemType) -> ItemTypeId {
+        let res = self.next_item_type_id;
+        self.next_item_type_id.0 += 1;
+        self.item_types.insert(res, value);
+        res
+    }
+
+    fn get_type(&self, type_id: ItemTypeId) -> &ItemType {
+        &self.item_types[&type_id]
+    }
+}
+
 pub trait Place<Tile> {
     fn place(&mut self, pos: impl Into<Vec2>, tile: Tile);
 }
@@ -264,6 +728,7 @@ impl Place<Floor> for State {
     fn place(&mut self, pos: impl Into<Vec2>, floor: Floor) {
         let Vec2 { x, y } = pos.into();
         self.floors.insert((x, y), floor);
+        self.changes.floors.insert((x, y));
     }
 }
 
@@ -271,6 +736,41 @@ impl Place<Wall> for State {
     fn place(&mut self, pos: impl Into<Vec2>, wall: Wall) {
         let Vec2 { x, y } = pos.into();
         self.walls.insert((x, y), wall);
+        self.changes.walls.insert((x, y));
+    }
+}
+
+impl Place<Decoration> for State {
+    fn place(&mut self, pos: impl Into<Vec2>, decoration: Decoration) {
+        let Vec2 { x, y } = pos.into();
+        self.decorations.insert((x, y), decoration);
+        self.changes.decorations.insert((x, y));
+    }
+}
+
+// stacked on top of the items already lying there
+impl Place<Item> for State {
+    fn place(&mut self, pos: impl Into<Vec2>, item: Item) {
+        let Vec2 { x, y } = pos.into();
+        self.items.entry((x, y)).or_default().push(item);
+        self.changes.items.insert((x, y));
+    }
+}
+
+impl State {
+    /// Entities standing on the tile are left there
+    pub fn remove_floor(&mut self, pos: impl Into<Vec2>) -> Option<Floor> {
+        let Vec2 { x, y } = pos.into();
+        let res = self.floors.remove(&(x, y))?;
+        self.changes.floors.insert((x, y));
+        Some(res)
+    }
+
+    pub fn remove_wall(&mut self, pos: impl Into<Vec2>) -> Option<Wall> {
+        let Vec2 { x, y } = pos.into();
+        let res = self.walls.remove(&(x, y))?;
+        self.changes.walls.insert((x, y));
+        Some(res)
     }
 }
 
@@ -318,11 +818,356 @@ impl State {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    Occupied,
+}
+
 impl State {
-    pub fn spawn(&mut self, entity: Entity) -> EntityId {
-        let res = self.next_entity_id;
-        self.next_entity_id.0 += 1;
+    pub fn check_spawn(&self, entity: &Entity) -> Result<(), SpawnError> {
+        if self.one_blocker_per_tile
+            && entity.is_blocking()
+            && self.blocking_entity_at(entity.pos).is_some()
+        {
+            return Err(SpawnError::Occupied);
+        }
+
+        Ok(())
+    }
+
+    pub fn spawn(&mut self, mut entity: Entity) -> Result<EntityId, SpawnError> {
+        self.init_health(&mut entity);
+        self.check_spawn(&entity)?;
+
+        let res = match self.free_entity_indices.pop() {
+            Some(index) => EntityId {
+                index,
+                generation: self.entity_generations[index as usize],
+            },
+
+            None => {
+                let index = self.entity_generations.len() as u32;
+                self.entity_generations.push(0);
+                EntityId {
+                    index,
+                    generation: 0,
+                }
+            }
+        };
+        self.occupy(res, entity.pos);
         self.entities.insert(res, entity);
-        res
+        self.changes.entities.insert(res);
+        Ok(res)
+    }
+
+    pub fn despawn(&mut self, entity_id: EntityId) -> Option<Entity> {
+        let res = self.entities.remove(&entity_id)?;
+        self.vacate(entity_id, res.pos);
+        self.changes.entities.insert(entity_id);
+
+        let generation = &mut self.entity_generations[entity_id.index as usize];
+        *generation = generation.wrapping_add(1);
+        self.free_entity_indices.push(entity_id.index);
+
+        Some(res)
+    }
+
+    /// Whether the entity has been despawned, even if its index has been taken by another one since
+    pub fn is_stale(&self, entity_id: EntityId) -> bool {
+        (self.entity_generations.get(entity_id.index as usize))
+            .is_some_and(|&generation| generation != entity_id.generation)
+    }
+}
+
+////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    NoEntity,
+    Dead,
+    NoFloor,
+    NotWalkable,
+    Wall,
+    Decoration,
+    Occupied,
+}
+
+impl State {
+    pub fn check_move(&self, entity_id: EntityId, direction: Direction) -> Result<Vec2, MoveError> {
+        let entity = self.entities.get(&entity_id).ok_or(MoveError::NoEntity)?;
+        if entity.is_dead() {
+            return Err(MoveError::Dead);
+        }
+        let target = entity.pos + direction.offset();
+        self.check_tile(target)?;
+        Ok(target)
+    }
+
+    /// Whether an entity could stand on `pos`
+    pub fn check_tile(&self, pos: Vec2) -> Result<(), MoveError> {
+        let key = (pos.x, pos.y);
+
+        let floor = self.floors.get(&key).ok_or(MoveError::NoFloor)?;
+        if !self.get_type(floor.type_id).walkable {
+            return Err(MoveError::NotWalkable);
+        }
+
+        if self.walls.contains_key(&key) {
+            return Err(MoveError::Wall);
+        }
+
+        if let Some(decoration) = self.decorations.get(&key)
+            && self.get_type(decoration.type_id).solid
+        {
+            return Err(MoveError::Decoration);
+        }
+
+        if self.one_blocker_per_tile && self.blocking_entity_at(pos).is_some() {
+            return Err(MoveError::Occupied);
+        }
+
+        Ok(())
+    }
+
+    pub fn move_entity(
+        &mut self,
+        entity_id: EntityId,
+        direction: Direction,
+    ) -> Result<(), MoveError> {
+        let target = self.check_move(entity_id, direction)?;
+        let from = self.entities[&entity_id].pos;
+        self.set_pos(entity_id, target);
+        self.push_event(PluginEvent::EntityMoved {
+            entity_id,
+            from,
+            to: target,
+        });
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigError {
+    NoEntity,
+    Dead,
+    NoWall,
+    Unbreakable,
+    NoTool,
+    WrongTool,
+}
+
+impl State {
+    pub fn check_dig(&self, entity_id: EntityId, direction: Direction) -> Result<Vec2, DigError> {
+        let entity = self.entities.get(&entity_id).ok_or(DigError::NoEntity)?;
+        if entity.is_dead() {
+            return Err(DigError::Dead);
+        }
+        let target = entity.pos + direction.offset();
+
+        let wall = (self.walls.get(&(target.x, target.y))).ok_or(DigError::NoWall)?;
+        let breakable = (self.get_type(wall.type_id).breakable).ok_or(DigError::Unbreakable)?;
+
+        let tool_kind =
+            (self.equipped_tool(entity_id, EquipSlot::DiggingTool)).ok_or(DigError::NoTool)?;
+        if tool_kind != breakable.tool_kind {
+            return Err(DigError::WrongTool);
+        }
+
+        Ok(target)
+    }
+
+    /// Returns whether the wall was broken by this hit
+    pub fn dig(&mut self, entity_id: EntityId, direction: Direction) -> Result<bool, DigError> {
+        let target = self.check_dig(entity_id, direction)?;
+        let key = (target.x, target.y);
+
+        let wall = self.walls.get_mut(&key).ok_or(DigError::NoWall)?;
+        let breakable = (self.wall_types[&wall.type_id].breakable).ok_or(DigError::Unbreakable)?;
+        wall.damage += 1;
+        let broken = wall.damage >= breakable.hits;
+
+        if broken {
+            self.remove_wall(target);
+        } else {
+            self.changes.walls.insert(key);
+        }
+
+        self.push_event(PluginEvent::WallHit {
+            entity_id,
+            pos: target,
+        });
+        if broken {
+            self.push_event(PluginEvent::WallBroken {
+                entity_id,
+                pos: target,
+            });
+        }
+
+        Ok(broken)
+    }
+}
+
+////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractError {
+    NoEntity,
+    Dead,
+    NoDecoration,
+    NotInteractable,
+}
+
+impl State {
+    pub fn check_interact(
+        &self,
+        entity_id: EntityId,
+        direction: Direction,
+    ) -> Result<Vec2, InteractError> {
+        let entity = (self.entities.get(&entity_id)).ok_or(InteractError::NoEntity)?;
+        if entity.is_dead() {
+            return Err(InteractError::Dead);
+        }
+        let target = entity.pos + direction.offset();
+
+        let decoration =
+            (self.decorations.get(&(target.x, target.y))).ok_or(InteractError::NoDecoration)?;
+        if !self.get_type(decoration.type_id).interactable {
+            return Err(InteractError::NotInteractable);
+        }
+
+        Ok(target)
+    }
+
+    /// Opens containers, anything else is up to plugins handling `PluginEvent::DecorationInteracted`
+    pub fn interact(
+        &mut self,
+        entity_id: EntityId,
+        direction: Direction,
+    ) -> Result<(), InteractError> {
+        let target = self.check_interact(entity_id, direction)?;
+        self.open_container(entity_id, target);
+
+        self.push_event(PluginEvent::DecorationInteracted {
+            entity_id,
+            pos: target,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn stale_ids_dont_refer_to_new_entities() {
+        let mut state = State::default();
+        let dummy = state.insert_type(EntityType::new("dummy"));
+
+        let old = state.spawn(dummy.instance(vec2(0, 0))).unwrap();
+        state.despawn(old);
+        let new = state.spawn(dummy.instance(vec2(5, 5))).unwrap();
+
+        assert_eq!(old.index(), new.index());
+        assert_ne!(old, new);
+        assert!(state.is_stale(old));
+        assert!(!state.is_stale(new));
+        assert_eq!(state.despawn(old).map(|entity| entity.pos), None);
+        assert_eq!(state.entities()[&new].pos, vec2(5, 5));
+    }
+
+    #[test]
+    fn removed_tiles_are_recorded_as_changes() {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let stone = state.insert_type(WallType::new("stone"));
+        state.place((0, 0), grass.instance());
+        state.place((0, 0), stone.instance());
+        state.take_changes();
+
+        assert!(state.remove_wall((0, 0)).is_some());
+        assert_eq!(state.check_tile(vec2(0, 0)), Ok(()));
+        assert!(state.remove_floor((0, 0)).is_some());
+        assert_eq!(state.check_tile(vec2(0, 0)), Err(MoveError::NoFloor));
+        assert!(state.remove_floor((0, 0)).is_none());
+
+        let changes = state.take_changes();
+        assert!(changes.floors.contains(&(0, 0)));
+        assert!(changes.walls.contains(&(0, 0)));
+    }
+
+    #[test]
+    fn moves_are_delivered_to_plugins() {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        state.place_rect((0, 0), (1, 0), grass.instance());
+        let player = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+        state.take_events();
+
+        state.move_entity(player, Direction::Right).unwrap();
+        assert!(matches!(
+            state.take_events().as_slice(),
+            [PluginEvent::EntityMoved { entity_id, from, to }]
+                if *entity_id == player && *from == vec2(0, 0) && *to == vec2(1, 0)
+        ));
+    }
+
+    // a player facing a wall of `wall_type` on its right
+    fn quarry(wall_type: WallType) -> (State, EntityId) {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let wall = state.insert_type(wall_type);
+        state.place_rect((0, 0), (1, 0), grass.instance());
+        state.place((1, 0), wall.instance());
+        let player = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+        (state, player)
+    }
+
+    fn give_tool(state: &mut State, player: EntityId, tool_kind: ToolKind) {
+        let tool = state.insert_type(ItemType::new("tool").tool(tool_kind));
+        state.give_item(player, tool.instance()).unwrap();
+    }
+
+    #[test]
+    fn digging_needs_the_right_tool() {
+        let (mut state, player) = quarry(WallType::new("dirt").breakable(ToolKind::Shovel, 1));
+        assert_eq!(state.dig(player, Direction::Right), Err(DigError::NoTool));
+
+        give_tool(&mut state, player, ToolKind::Pickaxe);
+        assert_eq!(
+            state.dig(player, Direction::Right),
+            Err(DigError::WrongTool)
+        );
+        assert!(state.walls().contains_key(&(1, 0)));
+    }
+
+    #[test]
+    fn walls_break_on_the_last_hit() {
+        let (mut state, player) = quarry(WallType::new("stone").breakable(ToolKind::Pickaxe, 3));
+        give_tool(&mut state, player, ToolKind::Pickaxe);
+
+        for hits in 1..3 {
+            assert_eq!(state.dig(player, Direction::Right), Ok(false));
+            assert_eq!(state.walls().get(&(1, 0)).unwrap().damage, hits);
+        }
+
+        assert_eq!(state.dig(player, Direction::Right), Ok(true));
+        assert!(!state.walls().contains_key(&(1, 0)));
+        assert_eq!(state.dig(player, Direction::Right), Err(DigError::NoWall));
+    }
+
+    #[test]
+    fn zero_hit_walls_break_on_the_first_hit() {
+        let (mut state, player) = quarry(WallType::new("sand").breakable(ToolKind::Shovel, 0));
+        give_tool(&mut state, player, ToolKind::Shovel);
+
+        assert_eq!(state.dig(player, Direction::Right), Ok(true));
+        assert!(!state.walls().contains_key(&(1, 0)));
     }
 }
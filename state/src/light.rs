@@ -0,0 +1,138 @@
+use crate::{EntityId, ObjectType, State, Vec2, vec2};
+use std::collections::HashMap;
+
+/// Light levels of every lit tile, tiles that aren't in it are completely dark
+pub type LightMap = HashMap<(i32, i32), u8>;
+
+/// Given off by floors, walls, decorations, entities and items, fades out linearly towards `radius`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Light {
+    pub radius: i32,
+    pub brightness: u8,
+}
+
+impl Light {
+    fn level_at(self, offset: Vec2) -> u8 {
+        let distance = f64::from(offset.x * offset.x + offset.y * offset.y).sqrt();
+        let falloff = (1.0 - distance / f64::from(self.radius + 1)).max(0.0);
+        (f64::from(self.brightness) * falloff).round() as u8
+    }
+}
+
+impl State {
+    /// The widest light among the equipped items
+    pub fn held_light(&self, entity_id: EntityId) -> Option<Light> {
+        let entity = self.entities.get(&entity_id)?;
+        (entity.inventory.equipped.values())
+            .filter_map(|item| self.get_type(item.type_id).light)
+            .max_by_key(|light| (light.radius, light.brightness))
+    }
+
+    fn light_sources(&self) -> Vec<(Vec2, Light)> {
+        let floors = (self.floors.iter())
+            .filter_map(|((x, y), floor)| Some((vec2(x, y), self.get_type(floor.type_id).light?)));
+        let walls = (self.walls.iter())
+            .filter_map(|((x, y), wall)| Some((vec2(x, y), self.get_type(wall.type_id).light?)));
+        let decorations = (self.decorations.iter()).filter_map(|(&(x, y), decoration)| {
+            Some((vec2(x, y), self.get_type(decoration.type_id).light?))
+        });
+        let items = (self.items.iter()).flat_map(|(&(x, y), items)| {
+            (items.iter())
+                .filter_map(move |item| Some((vec2(x, y), self.get_type(item.type_id).light?)))
+        });
+
+        let entities = (self.entities.iter())
+            .filter(|(_, entity)| !entity.is_dead())
+            .flat_map(|(&entity_id, entity)| {
+                let own = self.get_type(entity.type_id).light;
+                let held = self.held_light(entity_id);
+                own.into_iter().chain(held).map(|light| (entity.pos, light))
+            });
+
+        floors
+            .chain(walls)
+            .chain(decorations)
+            .chain(items)
+            .chain(entities)
+            .collect()
+    }
+
+    /// Lights add up, opaque walls get lit but cast shadows behind them
+    pub fn light_map(&self) -> LightMap {
+        let mut res = LightMap::new();
+        for (origin, light) in self.light_sources() {
+            for (x, y) in self.field_of_view(origin, light.radius) {
+                let level = light.level_at(vec2(x, y) - origin);
+                let total = res.entry((x, y)).or_default();
+                *total = total.saturating_add(level);
+            }
+        }
+
+        res.retain(|_, level| *level != 0);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    // a torch-holding player at the left end of a corridor with a wall in the middle
+    fn corridor() -> (State, EntityId) {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let stone = state.insert_type(WallType::new("stone"));
+        let torch = state.insert_type(
+            ItemType::new("torch")
+                .equippable(EquipSlot::Torch)
+                .light(4, 200),
+        );
+
+        state.place_rect((0, 0), (9, 0), grass.instance());
+        state.place((3, 0), stone.instance());
+        let player = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+        state.give_item(player, torch.instance()).unwrap();
+
+        (state, player)
+    }
+
+    #[test]
+    fn light_fades_with_distance_and_stops_at_walls() {
+        let (state, _) = corridor();
+        let light = state.light_map();
+
+        assert_eq!(light[&(0, 0)], 200);
+        assert!(light[&(1, 0)] > light[&(2, 0)]);
+        assert!(light.contains_key(&(3, 0)));
+        assert!(!light.contains_key(&(4, 0)));
+    }
+
+    #[test]
+    fn lights_add_up() {
+        let (mut state, _) = corridor();
+        let lamp = state.insert_type(FloorType::new("lamp").light(1, 100));
+        state.place((1, 0), lamp.instance());
+
+        let light = state.light_map();
+        assert_eq!(light[&(1, 0)], u8::MAX);
+        assert!(light[&(2, 0)] > 100);
+    }
+
+    #[test]
+    fn held_lights_move_with_their_entity() {
+        let (mut state, player) = corridor();
+        assert_eq!(
+            state.held_light(player),
+            Some(Light {
+                radius: 4,
+                brightness: 200
+            })
+        );
+
+        state.move_entity(player, Direction::Right).unwrap();
+        let light = state.light_map();
+        assert_eq!(light[&(1, 0)], 200);
+    }
+}
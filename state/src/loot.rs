@@ -0,0 +1,158 @@
+use crate::{EntityId, Item, ItemTypeId, Rng, State, Vec2};
+use std::mem::take;
+
+#[derive(Debug, Clone)]
+pub struct LootEntry {
+    // `None` for rolling nothing
+    pub item_type_id: Option<ItemTypeId>,
+    pub weight: u64,
+}
+
+/// Each roll picks one entry with a probability proportional to its weight
+#[derive(Debug, Clone)]
+pub struct LootTable {
+    pub rolls: usize,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    pub const fn new(rolls: usize) -> Self {
+        Self {
+            rolls,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn entry(mut self, item_type_id: ItemTypeId, weight: u64) -> Self {
+        self.entries.push(LootEntry {
+            item_type_id: Some(item_type_id),
+            weight,
+        });
+        self
+    }
+
+    pub fn nothing(mut self, weight: u64) -> Self {
+        self.entries.push(LootEntry {
+            item_type_id: None,
+            weight,
+        });
+        self
+    }
+
+    pub fn roll(&self, rng: &mut Rng) -> Vec<Item> {
+        let total_weight = self.entries.iter().map(|entry| entry.weight).sum();
+        if total_weight == 0 {
+            return Vec::default();
+        }
+
+        (0..self.rolls)
+            .filter_map(|_| {
+                let mut pick = rng.below(total_weight);
+                let entry = self.entries.iter().find(|entry| {
+                    if pick < entry.weight {
+                        return true;
+                    }
+                    pick -= entry.weight;
+                    false
+                })?;
+                entry.item_type_id.map(ItemTypeId::instance)
+            })
+            .collect()
+    }
+}
+
+/// The loot table is rolled once, when the container is opened for the first time
+#[derive(Debug, Clone, Default)]
+pub struct Container {
+    pub items: Vec<Item>,
+    pub loot_table: Option<LootTable>,
+}
+
+impl State {
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /// Moves the contents into the opener's inventory, the overflow ends up on the opener's tile
+    pub(crate) fn open_container(&mut self, entity_id: EntityId, pos: Vec2) {
+        if !self.entities.contains_key(&entity_id) {
+            return;
+        }
+
+        let Some(container) = (self.decorations.get_mut(&(pos.x, pos.y)))
+            .and_then(|decoration| decoration.container.as_mut())
+        else {
+            return;
+        };
+
+        let mut items = take(&mut container.items);
+        if let Some(loot_table) = container.loot_table.take() {
+            items.extend(loot_table.roll(&mut self.rng));
+        }
+
+        for item in items {
+            (self.give_item(entity_id, item)).expect("the opener has been checked for above");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn type_ids(items: &[Item]) -> Vec<ItemTypeId> {
+        items.iter().map(|item| item.type_id).collect()
+    }
+
+    #[test]
+    fn rolls_follow_the_weights() {
+        let mut state = State::default();
+        let common = state.insert_type(ItemType::new("common"));
+        let rare = state.insert_type(ItemType::new("rare"));
+        let never = state.insert_type(ItemType::new("never"));
+        let loot_table = LootTable::new(1000)
+            .entry(common, 3)
+            .entry(rare, 1)
+            .entry(never, 0)
+            .nothing(4);
+
+        let items = loot_table.roll(&mut Rng::new(7));
+        let count = |type_id| items.iter().filter(|item| item.type_id == type_id).count();
+        assert!((325..425).contains(&count(common)));
+        assert!((75..175).contains(&count(rare)));
+        assert_eq!(count(never), 0);
+
+        let again = loot_table.roll(&mut Rng::new(7));
+        assert_eq!(type_ids(&items), type_ids(&again));
+    }
+
+    #[test]
+    fn chests_are_emptied_into_the_opener_once() {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let chest = state.insert_type(DecorationType::new("chest").solid().interactable());
+        let sword = state.insert_type(ItemType::new("sword"));
+        let gem = state.insert_type(ItemType::new("gem"));
+
+        state.place_rect((0, 0), (1, 0), grass.instance());
+        state.place(
+            (1, 0),
+            (chest.instance())
+                .item(sword.instance())
+                .loot_table(LootTable::new(2).entry(gem, 1)),
+        );
+        let player = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+
+        state.interact(player, Direction::Right).unwrap();
+        state.interact(player, Direction::Right).unwrap();
+
+        let bag = (state.inventory(player).unwrap().bag.iter())
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(type_ids(&bag), [sword, gem, gem]);
+        assert!(state.items().is_empty());
+    }
+}
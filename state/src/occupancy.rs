@@ -0,0 +1,171 @@
+use crate::{Entity, EntityId, State, Vec2, vec2};
+
+impl State {
+    /// Whether at most one blocking entity may stand on a tile, which is the case by default
+    pub const fn set_one_blocker_per_tile(&mut self, enabled: bool) {
+        self.one_blocker_per_tile = enabled;
+    }
+
+    /// In the order they've come onto the tile
+    pub fn entities_at(&self, pos: impl Into<Vec2>) -> &[EntityId] {
+        let Vec2 { x, y } = pos.into();
+        (self.occupancy.get(&(x, y))).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn blocking_entity_at(&self, pos: impl Into<Vec2>) -> Option<EntityId> {
+        (self.entities_at(pos).iter())
+            .copied()
+            .find(|entity_id| self.entities[entity_id].is_blocking())
+    }
+
+    /// Entities inside the rectangle from `min` to `max`, both included
+    pub fn entities_in_rect(
+        &self,
+        min: impl Into<Vec2>,
+        max: impl Into<Vec2>,
+    ) -> impl Iterator<Item = EntityId> {
+        (self.occupancy.region(min, max)).flat_map(|(_, entity_ids)| entity_ids.iter().copied())
+    }
+
+    /// The closest entity for which `filter` returns `true`, ties are broken by the lower id
+    pub fn nearest_entity(
+        &self,
+        pos: impl Into<Vec2>,
+        filter: impl Fn(EntityId, &Entity) -> bool,
+    ) -> Option<EntityId> {
+        let pos = pos.into();
+        let distance = |entity_id: EntityId| {
+            let offset = self.entities[&entity_id].pos - pos;
+            i64::from(offset.x).pow(2) + i64::from(offset.y).pow(2)
+        };
+
+        // squares around `pos` growing until they have an entity that's closer than their border
+        let mut radius = 8;
+        loop {
+            let min = vec2(pos.x.saturating_sub(radius), pos.y.saturating_sub(radius));
+            let max = vec2(pos.x.saturating_add(radius), pos.y.saturating_add(radius));
+
+            let mut seen = 0;
+            let nearest = (self.entities_in_rect(min, max))
+                .inspect(|_| seen += 1)
+                .filter(|&entity_id| filter(entity_id, &self.entities[&entity_id]))
+                .min_by_key(|&entity_id| (distance(entity_id), entity_id));
+
+            let found_closest = nearest.is_some_and(|entity_id| {
+                distance(entity_id) <= i64::from(radius) * i64::from(radius)
+            });
+            if found_closest || seen == self.entities.len() || radius == i32::MAX {
+                return nearest;
+            }
+
+            radius = radius.saturating_mul(2);
+        }
+    }
+
+    // the only way entities get moved, so that `occupancy` is kept in sync
+    pub(crate) fn set_pos(&mut self, entity_id: EntityId, pos: Vec2) {
+        let Some(entity) = self.entities.get_mut(&entity_id) else {
+            return;
+        };
+        let from = entity.pos;
+        entity.pos = pos;
+
+        self.vacate(entity_id, from);
+        self.occupy(entity_id, pos);
+        self.changes.entities.insert(entity_id);
+    }
+
+    pub(crate) fn occupy(&mut self, entity_id: EntityId, pos: Vec2) {
+        match self.occupancy.get_mut(&(pos.x, pos.y)) {
+            Some(entity_ids) => entity_ids.push(entity_id),
+            None => {
+                self.occupancy.insert((pos.x, pos.y), vec![entity_id]);
+            }
+        }
+    }
+
+    pub(crate) fn vacate(&mut self, entity_id: EntityId, pos: Vec2) {
+        let key = (pos.x, pos.y);
+        let Some(entity_ids) = self.occupancy.get_mut(&key) else {
+            return;
+        };
+
+        entity_ids.retain(|&other| other != entity_id);
+        if entity_ids.is_empty() {
+            self.occupancy.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn field() -> (State, EntityTypeId) {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let dummy = state.insert_type(EntityType::new("dummy").health(1));
+        state.place_rect((-50, -50), (50, 50), grass.instance());
+        (state, dummy)
+    }
+
+    #[test]
+    fn the_index_follows_entities() {
+        let (mut state, dummy) = field();
+        let first = state.spawn(dummy.instance(vec2(0, 0))).unwrap();
+        let second = state.spawn(dummy.instance(vec2(1, 1))).unwrap();
+        assert_eq!(state.entities_at((0, 0)), [first]);
+
+        state.move_entity(first, Direction::Right).unwrap();
+        assert_eq!(state.entities_at((0, 0)), []);
+        assert_eq!(state.entities_at((1, 0)), [first]);
+
+        let mut in_rect = state.entities_in_rect((0, 0), (1, 1)).collect::<Vec<_>>();
+        in_rect.sort();
+        assert_eq!(in_rect, [first, second]);
+
+        state.despawn(first);
+        assert_eq!(state.entities_at((1, 0)), []);
+        assert_eq!(state.entities_in_rect((0, 0), (1, 1)).count(), 1);
+    }
+
+    #[test]
+    fn the_nearest_entity_passes_the_filter() {
+        let (mut state, dummy) = field();
+        let player = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+        let near = state.spawn(dummy.instance(vec2(3, 4))).unwrap();
+        let far = state.spawn(dummy.instance(vec2(-40, 30))).unwrap();
+
+        let not_player = |entity_id, _: &Entity| entity_id != player;
+        assert_eq!(state.nearest_entity((0, 0), not_player), Some(near));
+        assert_eq!(state.nearest_entity((-30, 30), not_player), Some(far));
+        assert_eq!(
+            state.nearest_entity((0, 0), |entity_id, _| entity_id == far),
+            Some(far)
+        );
+        assert_eq!(state.nearest_entity((0, 0), |_, _| false), None);
+    }
+
+    #[test]
+    fn blocking_entities_dont_share_tiles() {
+        let (mut state, dummy) = field();
+        let first = state.spawn(dummy.instance(vec2(0, 0))).unwrap();
+        let second = state.spawn(dummy.instance(vec2(1, 0))).unwrap();
+
+        assert_eq!(
+            state.spawn(dummy.instance(vec2(0, 0))),
+            Err(SpawnError::Occupied)
+        );
+        assert_eq!(
+            state.move_entity(second, Direction::Left),
+            Err(MoveError::Occupied)
+        );
+
+        state.set_one_blocker_per_tile(false);
+        state.move_entity(second, Direction::Left).unwrap();
+        assert_eq!(state.entities_at((0, 0)), [first, second]);
+        assert_eq!(state.blocking_entity_at((0, 0)), Some(first));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{Direction, MoveError, State, Vec2};
+use std::collections::{HashMap, VecDeque};
+
+impl State {
+    /// Returns the directions of the shortest path from `from` to `to` that's at most `max_length` steps long
+    ///
+    /// `to` itself may be occupied, so that entities can find their way to each other
+    pub fn find_path(&self, from: Vec2, to: Vec2, max_length: usize) -> Option<Vec<Direction>> {
+        // the previous tile and the step from it for every reached tile
+        let mut came_from = HashMap::from([((from.x, from.y), None)]);
+        let mut queue = VecDeque::from([(from, 0)]);
+
+        while let Some((pos, length)) = queue.pop_front() {
+            if pos == to {
+                let mut path = Vec::new();
+                let mut key = (pos.x, pos.y);
+                while let Some(&Some((previous, direction))) = came_from.get(&key) {
+                    path.push(direction);
+                    key = previous;
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            if length == max_length {
+                continue;
+            }
+
+            for direction in Direction::ALL {
+                let next = pos + direction.offset();
+                let key = (next.x, next.y);
+                if came_from.contains_key(&key) {
+                    continue;
+                }
+
+                match self.check_tile(next) {
+                    Ok(()) => {}
+                    Err(MoveError::Occupied) if next == to => {}
+                    Err(_) => continue,
+                }
+
+                came_from.insert(key, Some(((pos.x, pos.y), direction)));
+                queue.push_back((next, length + 1));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    // a 5x5 room with a wall across the middle row, leaving a gap on the right
+    fn room() -> State {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let stone = state.insert_type(WallType::new("stone"));
+
+        state.place_rect((0, 0), (4, 4), grass.instance());
+        state.place_rect((0, 2), (3, 2), stone.instance());
+        state
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let state = room();
+        let path = state.find_path(vec2(0, 0), vec2(0, 4), 16).unwrap();
+
+        assert_eq!(path.len(), 12);
+        let end = (path.iter()).fold(vec2(0, 0), |pos, direction| pos + direction.offset());
+        assert_eq!(end, vec2(0, 4));
+    }
+
+    #[test]
+    fn paths_avoid_unwalkable_floors_and_entities() {
+        let mut state = room();
+        let water = state.insert_type(FloorType::new("water").non_walkable());
+        state.place((4, 1), water.instance());
+        assert_eq!(state.find_path(vec2(0, 0), vec2(0, 4), 32), None);
+
+        let mut state = room();
+        state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(4, 2)))
+            .unwrap();
+        assert_eq!(state.find_path(vec2(0, 0), vec2(0, 4), 32), None);
+    }
+
+    #[test]
+    fn paths_may_end_on_entities() {
+        let mut state = room();
+        state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(2, 0)))
+            .unwrap();
+        assert_eq!(
+            state.find_path(vec2(0, 0), vec2(2, 0), 2),
+            Some(vec![Direction::Right, Direction::Right])
+        );
+    }
+
+    #[test]
+    fn paths_are_limited_in_length() {
+        let state = room();
+        assert_eq!(state.find_path(vec2(0, 0), vec2(0, 4), 11), None);
+    }
+}
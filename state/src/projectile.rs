@@ -0,0 +1,219 @@
+use crate::{Direction, EntityId, EntityTypeId, ObjectType, PluginEvent, State};
+
+/// What a ranged weapon shoots
+#[derive(Debug, Clone, Copy)]
+pub struct ProjectileType {
+    pub type_id: EntityTypeId,
+    // tiles travelled per tick
+    pub speed: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Projectile {
+    pub direction: Direction,
+    pub speed: u32,
+    pub damage: u32,
+    pub source: EntityId,
+}
+
+impl State {
+    // the projectile starts on the shooter's tile and flies from the next `tick_projectiles` on, like any other
+    pub(crate) fn shoot(
+        &mut self,
+        entity_id: EntityId,
+        direction: Direction,
+        projectile_type: ProjectileType,
+        damage: u32,
+    ) -> Option<EntityId> {
+        let pos = self.entities[&entity_id].pos;
+        let mut entity = projectile_type.type_id.instance(pos);
+        entity.projectile = Some(Projectile {
+            direction,
+            speed: projectile_type.speed,
+            damage,
+            source: entity_id,
+        });
+
+        // never fails, since projectiles don't block
+        self.spawn(entity).ok()
+    }
+
+    /// Moves every projectile by its speed, meant to be called once per tick
+    pub fn tick_projectiles(&mut self) {
+        let mut projectile_ids = (self.entities.iter())
+            .filter(|(_, entity)| entity.projectile.is_some())
+            .map(|(&entity_id, _)| entity_id)
+            .collect::<Vec<_>>();
+        projectile_ids.sort();
+
+        for projectile_id in projectile_ids {
+            self.advance_projectile(projectile_id);
+        }
+    }
+
+    fn advance_projectile(&mut self, projectile_id: EntityId) {
+        let Some(projectile) =
+            (self.entities.get(&projectile_id)).and_then(|entity| entity.projectile)
+        else {
+            return;
+        };
+
+        for _ in 0..projectile.speed {
+            if !self.step_projectile(projectile_id, projectile) {
+                break;
+            }
+        }
+    }
+
+    // returns whether the projectile is still flying
+    fn step_projectile(&mut self, projectile_id: EntityId, projectile: Projectile) -> bool {
+        let Some(entity) = self.entities.get(&projectile_id) else {
+            return false;
+        };
+        let target = entity.pos + projectile.direction.offset();
+        let key = (target.x, target.y);
+
+        if let Some(wall) = self.walls.get(&key) {
+            if self.get_type(wall.type_id).shatters {
+                self.remove_wall(target);
+                self.push_event(PluginEvent::WallBroken {
+                    entity_id: projectile.source,
+                    pos: target,
+                });
+            }
+
+            self.despawn(projectile_id);
+            return false;
+        }
+
+        let blocked = !self.floors.contains_key(&key)
+            || (self.decorations.get(&key))
+                .is_some_and(|decoration| self.get_type(decoration.type_id).solid);
+        if blocked {
+            self.despawn(projectile_id);
+            return false;
+        }
+
+        let victim = (self.entities_at(target).iter())
+            .copied()
+            .find(|&entity_id| {
+                entity_id != projectile.source && self.entities[&entity_id].is_blocking()
+            });
+
+        if let Some(victim) = victim {
+            // invulnerable entities still stop the projectile
+            let _ = self.damage(victim, projectile.damage, Some(projectile.source));
+            self.despawn(projectile_id);
+            return false;
+        }
+
+        self.set_pos(projectile_id, target);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    struct Range {
+        state: State,
+        shooter: EntityId,
+        dummy: EntityTypeId,
+        glass: WallTypeId,
+    }
+
+    // a 10x1 corridor with a bow-wielding shooter on its left end
+    fn range() -> Range {
+        let mut state = State::default();
+        let grass = state.insert_type(FloorType::new("grass"));
+        let glass = state.insert_type(WallType::new("glass").shatters());
+        let dummy = state.insert_type(EntityType::new("dummy").health(3));
+        let arrow = state.insert_type(EntityType::new("arrow"));
+        let bow = state.insert_type(
+            ItemType::new("bow")
+                .tool(ToolKind::Bow)
+                .damage(1)
+                .shoots(arrow, 2),
+        );
+
+        state.place_rect((0, 0), (9, 0), grass.instance());
+        let shooter = state
+            .spawn(PLAYER_ENTITY_TYPE_ID.instance(vec2(0, 0)))
+            .unwrap();
+        state.give_item(shooter, bow.instance()).unwrap();
+
+        Range {
+            state,
+            shooter,
+            dummy,
+            glass,
+        }
+    }
+
+    fn projectiles(state: &State) -> Vec<Vec2> {
+        (state.entities().values())
+            .filter(|entity| entity.projectile.is_some())
+            .map(|entity| entity.pos)
+            .collect()
+    }
+
+    #[test]
+    fn projectiles_travel_their_speed_per_tick() {
+        let Range {
+            mut state, shooter, ..
+        } = range();
+
+        state.attack(shooter, Direction::Right).unwrap();
+        assert_eq!(projectiles(&state), [vec2(0, 0)]);
+
+        state.tick_projectiles();
+        assert_eq!(projectiles(&state), [vec2(2, 0)]);
+
+        state.tick_projectiles();
+        assert_eq!(projectiles(&state), [vec2(4, 0)]);
+
+        for _ in 0..3 {
+            state.tick_projectiles();
+        }
+        assert!(projectiles(&state).is_empty());
+    }
+
+    #[test]
+    fn projectiles_hit_the_first_entity() {
+        let Range {
+            mut state,
+            shooter,
+            dummy,
+            ..
+        } = range();
+        let first = state.spawn(dummy.instance(vec2(3, 0))).unwrap();
+        let second = state.spawn(dummy.instance(vec2(4, 0))).unwrap();
+
+        state.attack(shooter, Direction::Right).unwrap();
+        state.tick_projectiles();
+        state.tick_projectiles();
+
+        assert_eq!(state.entities()[&first].health.unwrap().current, 2);
+        assert_eq!(state.entities()[&second].health.unwrap().current, 3);
+        assert!(projectiles(&state).is_empty());
+    }
+
+    #[test]
+    fn projectiles_shatter_glass() {
+        let Range {
+            mut state,
+            shooter,
+            glass,
+            ..
+        } = range();
+        state.place((3, 0), glass.instance());
+
+        state.attack(shooter, Direction::Right).unwrap();
+        state.tick_projectiles();
+        state.tick_projectiles();
+
+        assert!(!state.walls().contains_key(&(3, 0)));
+        assert!(projectiles(&state).is_empty());
+    }
+}
@@ -0,0 +1,22 @@
+/// SplitMix64: the same seed always generates the same world
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub const fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut res = self.0;
+        res = (res ^ (res >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        res = (res ^ (res >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        res ^ (res >> 31)
+    }
+
+    /// `bound` has to be non-zero
+    pub const fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}